@@ -0,0 +1,172 @@
+use crate::{Demo, DemoId, User, UserId};
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Configuration for caching demos and users in memory
+///
+/// # Example
+///
+/// ```rust
+/// use demostf_client::{ApiClient, CacheConfig};
+/// use std::time::Duration;
+///
+/// let client = ApiClient::new().with_cache(CacheConfig {
+///     capacity: 1000,
+///     ttl: Duration::from_secs(300),
+/// });
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheConfig {
+    /// Maximum number of demos and of users kept in the cache
+    pub capacity: usize,
+    /// How long cached data is used before it is requested again
+    pub ttl: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            capacity: 256,
+            ttl: Duration::from_secs(60),
+        }
+    }
+}
+
+/// Cache for demos and users, shared between clones of a client
+#[derive(Debug)]
+pub(crate) struct Cache {
+    demos: Mutex<Lru<DemoId, Demo>>,
+    users: Mutex<Lru<UserId, User>>,
+}
+
+impl Cache {
+    pub fn new(config: CacheConfig) -> Self {
+        Cache {
+            demos: Mutex::new(Lru::new(config)),
+            users: Mutex::new(Lru::new(config)),
+        }
+    }
+
+    pub fn demo(&self, id: DemoId) -> Option<Demo> {
+        lock(&self.demos).get(id, Instant::now())
+    }
+
+    pub fn insert_demo(&self, demo: Demo) {
+        lock(&self.demos).insert(demo.id, demo, Instant::now());
+    }
+
+    pub fn invalidate_demo(&self, id: DemoId) {
+        lock(&self.demos).remove(id);
+    }
+
+    pub fn user(&self, id: UserId) -> Option<User> {
+        lock(&self.users).get(id, Instant::now())
+    }
+
+    pub fn insert_user(&self, user: User) {
+        lock(&self.users).insert(user.id, user, Instant::now());
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> std::sync::MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    inserted: Instant,
+    last_used: u64,
+}
+
+/// Least recently used cache with expiring entries
+///
+/// Evicting an entry scans the whole cache, which is fine for the small capacities this is used with.
+#[derive(Debug)]
+struct Lru<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    config: CacheConfig,
+    /// Counter used to track the order in which entries are used
+    tick: u64,
+}
+
+impl<K: Copy + Eq + Hash, V: Clone> Lru<K, V> {
+    fn new(config: CacheConfig) -> Self {
+        Lru {
+            entries: HashMap::new(),
+            config,
+            tick: 0,
+        }
+    }
+
+    fn get(&mut self, key: K, now: Instant) -> Option<V> {
+        self.tick += 1;
+        let entry = self.entries.get_mut(&key)?;
+        if now.saturating_duration_since(entry.inserted) >= self.config.ttl {
+            self.entries.remove(&key);
+            return None;
+        }
+        entry.last_used = self.tick;
+        Some(entry.value.clone())
+    }
+
+    fn insert(&mut self, key: K, value: V, now: Instant) {
+        if self.config.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if !self.entries.contains_key(&key) && self.entries.len() >= self.config.capacity {
+            let ttl = self.config.ttl;
+            self.entries
+                .retain(|_, entry| now.saturating_duration_since(entry.inserted) < ttl);
+            if self.entries.len() >= self.config.capacity {
+                let oldest = self
+                    .entries
+                    .iter()
+                    .min_by_key(|(_, entry)| entry.last_used)
+                    .map(|(key, _)| *key);
+                if let Some(oldest) = oldest {
+                    self.entries.remove(&oldest);
+                }
+            }
+        }
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                inserted: now,
+                last_used: self.tick,
+            },
+        );
+    }
+
+    fn remove(&mut self, key: K) {
+        self.entries.remove(&key);
+    }
+}
+
+#[test]
+fn test_lru() {
+    let mut lru = Lru::new(CacheConfig {
+        capacity: 2,
+        ttl: Duration::from_secs(10),
+    });
+    let now = Instant::now();
+
+    lru.insert(1, "a", now);
+    lru.insert(2, "b", now);
+    assert_eq!(lru.get(1, now), Some("a"));
+
+    // 2 is the least recently used entry
+    lru.insert(3, "c", now);
+    assert_eq!(lru.get(2, now), None);
+    assert_eq!(lru.get(1, now), Some("a"));
+    assert_eq!(lru.get(3, now), Some("c"));
+
+    lru.remove(3);
+    assert_eq!(lru.get(3, now), None);
+
+    assert_eq!(lru.get(1, now + Duration::from_secs(10)), None);
+}
@@ -1,14 +1,75 @@
-use crate::{ChatMessage, Demo, Error, ListParams, User};
-use reqwest::{multipart, Client, IntoUrl, Response, StatusCode, Url};
+#[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+use crate::cache::{Cache, CacheConfig};
+use crate::json_array::JsonArrayParser;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::rate_limit::RateLimiter;
+use crate::{
+    ChatMessage, Demo, DemoId, DemoPage, Error, ListOrder, ListParams, Storage, User, UserId,
+};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::{Md5Context, RetryConfig};
+use futures_util::{stream, Stream, StreamExt, TryStreamExt};
+use reqwest::header::{
+    HeaderValue, ACCEPT_ENCODING, CONTENT_RANGE, RANGE, RETRY_AFTER, USER_AGENT,
+};
+use reqwest::{
+    multipart, Body, Client, IntoUrl, Method, RequestBuilder, Response, StatusCode, Url,
+};
+use serde::de::{DeserializeOwned, IgnoredAny};
+use serde_json::Value;
 use std::borrow::Borrow;
+use std::collections::{BTreeSet, HashMap, VecDeque};
 use std::fmt::{self, Debug, Formatter};
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 use steamid_ng::SteamID;
-use tracing::{instrument, trace};
+#[cfg(feature = "time")]
+use time::format_description::well_known::Rfc2822;
+#[cfg(feature = "time")]
+use time::OffsetDateTime;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::fs::File;
+#[cfg(not(target_arch = "wasm32"))]
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter};
+use tracing::{debug, debug_span, instrument, trace, Instrument, Span};
+
+/// Number of demos returned by demos.tf for a full page, used until a full page has been seen
+pub(crate) const PAGE_SIZE: usize = 50;
+
+/// Header used to send the access key for private demos
+///
+/// Header names with underscores are dropped by many proxies, so the server expects a dash here.
+const ACCESS_KEY_HEADER: &str = "ACCESS-KEY";
+
+/// Header used to send the correlation id of a request
+const REQUEST_ID_HEADER: &str = "X-Request-ID";
+
+/// Body of a response as a stream of chunks
+///
+/// Responses on wasm can only be read as a stream and aren't `Send`.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) type ByteStream =
+    std::pin::Pin<Box<dyn Stream<Item = Result<bytes::Bytes, reqwest::Error>> + Send>>;
+#[cfg(target_arch = "wasm32")]
+pub(crate) type ByteStream =
+    std::pin::Pin<Box<dyn Stream<Item = Result<bytes::Bytes, reqwest::Error>>>>;
+
+pub(crate) fn body_stream(response: Response) -> ByteStream {
+    Box::pin(response.bytes_stream())
+}
+
+/// Highest page that can contain any demos, demo ids are limited to 32bit signed integers
+const MAX_PAGE: u32 = i32::MAX as u32 / PAGE_SIZE as u32 + 1;
 
 /// Api client for demos.tf
 ///
+/// The access key of the client and the api keys passed to its methods are never included in the
+/// `Debug` output of the client or in the emitted tracing spans and events.
+///
 /// # Example
 ///
 /// ```rust
@@ -32,6 +93,18 @@ pub struct ApiClient {
     client: Client,
     base_url: Url,
     access_key: Option<String>,
+    user_agent: HeaderValue,
+    #[cfg(not(target_arch = "wasm32"))]
+    retry: Option<RetryConfig>,
+    options: RequestOptions,
+    #[cfg(not(target_arch = "wasm32"))]
+    rate_limit: Option<Arc<RateLimiter>>,
+    /// Generates the id sent with every request in the `X-Request-ID` header
+    request_id: Option<Arc<dyn Fn() -> String + Send + Sync>>,
+    /// Largest number of demos returned in a single page, 0 if no demos have been listed yet
+    page_size: Arc<AtomicU32>,
+    #[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+    cache: Option<Arc<Cache>>,
 }
 
 impl Default for ApiClient {
@@ -48,13 +121,253 @@ impl Debug for ApiClient {
     }
 }
 
+/// Options that override the client defaults for individual requests
+///
+/// # Example
+///
+/// ```rust
+/// use demostf_client::{ApiClient, ListParams, RequestOptions};
+/// use std::time::Duration;
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), demostf_client::Error> {
+/// # let client = ApiClient::default();
+/// #
+/// let demos = client
+///     .with_options(RequestOptions::default().with_timeout(Duration::from_secs(60)))
+///     .list(ListParams::default().with_players([76561198024494988]), 1)
+///     .await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RequestOptions {
+    /// Timeout for the requests, overriding the timeout of the client
+    ///
+    /// For downloads, the timeout is scaled based on the demo duration
+    pub timeout: Option<Duration>,
+}
+
+impl RequestOptions {
+    /// Set the timeout for the requests
+    #[must_use]
+    pub fn with_timeout(self, timeout: Duration) -> Self {
+        RequestOptions {
+            timeout: Some(timeout),
+        }
+    }
+}
+
+/// Builder for an [`ApiClient`] with custom options
+///
+/// # Example
+///
+/// ```rust
+/// use demostf_client::ApiClient;
+/// use std::time::Duration;
+///
+/// # fn main() -> Result<(), demostf_client::Error> {
+/// let http_client = reqwest::Client::builder()
+///     .timeout(Duration::from_secs(30))
+///     .build()?;
+///
+/// let client = ApiClient::builder()
+///     .base_url("https://api.demos.tf/")
+///     .reqwest_client(http_client)
+///     .build()?;
+/// # Ok(())
+/// # }
+/// ```
+pub struct ApiClientBuilder {
+    base_url: Result<Url, Error>,
+    timeout: Duration,
+    access_key: Option<String>,
+    client: Option<Client>,
+    user_agent: String,
+    #[cfg(not(target_arch = "wasm32"))]
+    retry: Option<RetryConfig>,
+    #[cfg(not(target_arch = "wasm32"))]
+    rate_limit: Option<u32>,
+}
+
+impl Default for ApiClientBuilder {
+    fn default() -> Self {
+        ApiClientBuilder::new()
+    }
+}
+
+impl Debug for ApiClientBuilder {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiClientBuilder")
+            .field("timeout", &self.timeout)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ApiClientBuilder {
+    /// Create a builder for an api client for the default demos.tf endpoint
+    #[must_use]
+    pub fn new() -> Self {
+        ApiClientBuilder {
+            base_url: Url::parse(ApiClient::DEMOS_TF_BASE_URL).map_err(|_| Error::InvalidBaseUrl),
+            timeout: Duration::from_secs(15),
+            access_key: None,
+            client: None,
+            user_agent: ApiClient::DEFAULT_USER_AGENT.into(),
+            #[cfg(not(target_arch = "wasm32"))]
+            retry: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            rate_limit: None,
+        }
+    }
+
+    /// Use a different api endpoint
+    #[must_use]
+    pub fn base_url(self, base_url: impl IntoUrl) -> Self {
+        ApiClientBuilder {
+            base_url: base_url.into_url().map_err(|_| Error::InvalidBaseUrl),
+            ..self
+        }
+    }
+
+    /// Set the timeout for requests, download timeouts are scaled from this based on the demo duration
+    ///
+    /// When a custom `reqwest::Client` is provided, this timeout is only used for downloads
+    #[must_use]
+    pub fn timeout(self, timeout: Duration) -> Self {
+        ApiClientBuilder { timeout, ..self }
+    }
+
+    /// Set access key used to access private demos
+    ///
+    /// The access key is also used as key by the `*_with_stored_key` methods.
+    #[must_use]
+    pub fn access_key(self, access_key: impl Into<String>) -> Self {
+        ApiClientBuilder {
+            access_key: Some(access_key.into()),
+            ..self
+        }
+    }
+
+    /// Use a preconfigured `reqwest::Client` instead of creating a new one
+    #[must_use]
+    pub fn reqwest_client(self, client: Client) -> Self {
+        ApiClientBuilder {
+            client: Some(client),
+            ..self
+        }
+    }
+
+    /// Set the user agent send with every request, defaults to `demostf-client/{version}`
+    #[must_use]
+    pub fn user_agent(self, user_agent: impl Into<String>) -> Self {
+        ApiClientBuilder {
+            user_agent: user_agent.into(),
+            ..self
+        }
+    }
+
+    /// Retry idempotent requests that fail with a server error or timeout
+    ///
+    /// Only requests that don't modify any data (listing and getting demos, users and chat) are retried,
+    /// uploading a demo or changing the demo url is never retried.
+    ///
+    /// Rate limited requests are retried after the delay requested by the server, if that delay is longer
+    /// than `max_delay` the request isn't retried and [`Error::RateLimited`] is returned instead.
+    ///
+    /// Retries are delayed using Tokio timers, which requires the client to be used from a Tokio runtime.
+    /// Retrying isn't available on wasm.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn retry(self, retry: RetryConfig) -> Self {
+        ApiClientBuilder {
+            retry: Some(retry),
+            ..self
+        }
+    }
+
+    /// Limit the number of requests send per second
+    ///
+    /// The limit is shared between all clones of the client, requests that would exceed the limit
+    /// wait until they can be send. Short bursts of up to `requests_per_second` requests are allowed.
+    ///
+    /// Rate limiting requires a Tokio runtime and isn't available on wasm.
+    #[cfg(not(target_arch = "wasm32"))]
+    #[must_use]
+    pub fn rate_limit(self, requests_per_second: u32) -> Self {
+        ApiClientBuilder {
+            rate_limit: Some(requests_per_second),
+            ..self
+        }
+    }
+
+    /// Create the api client
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the provided base url is not a valid url, the user agent is not a valid header
+    /// or the http client can't be created
+    pub fn build(self) -> Result<ApiClient, Error> {
+        // ensure there is always a leading / to prevent unexpected behavior with url creation later
+        let mut base_url = self.base_url?;
+        if !base_url.path().ends_with("/") {
+            base_url.set_path(&format!("{}/", base_url.path()));
+        }
+
+        let user_agent =
+            HeaderValue::try_from(self.user_agent).map_err(|_| Error::InvalidUserAgent)?;
+
+        let client = match self.client {
+            Some(client) => client,
+            // timeouts can only be set per request on wasm
+            #[cfg(not(target_arch = "wasm32"))]
+            None => Client::builder().timeout(self.timeout).build()?,
+            #[cfg(target_arch = "wasm32")]
+            None => Client::builder().build()?,
+        };
+
+        Ok(ApiClient {
+            base_timeout: self.timeout,
+            client,
+            base_url,
+            access_key: self.access_key,
+            user_agent,
+            #[cfg(not(target_arch = "wasm32"))]
+            retry: self.retry,
+            options: RequestOptions::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            rate_limit: self
+                .rate_limit
+                .map(|limit| Arc::new(RateLimiter::new(limit))),
+            request_id: None,
+            page_size: Arc::default(),
+            #[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+            cache: None,
+        })
+    }
+}
+
 impl ApiClient {
     pub const DEMOS_TF_BASE_URL: &'static str = "https://api.demos.tf/";
+    pub const DEFAULT_USER_AGENT: &'static str =
+        concat!("demostf-client/", env!("CARGO_PKG_VERSION"));
+    /// Maximum number of concurrent requests made by `get_users`
+    pub const USER_CONCURRENCY: usize = 8;
+    /// Maximum time `ping` waits for the api to respond
+    pub const PING_TIMEOUT: Duration = Duration::from_secs(5);
 
     /// Create an api client for the default demos.tf endpoint
     #[must_use]
     pub fn new() -> Self {
-        ApiClient::with_base_url(ApiClient::DEMOS_TF_BASE_URL).unwrap_or_else(|_| unreachable!())
+        ApiClient::builder()
+            .build()
+            .unwrap_or_else(|_| unreachable!())
+    }
+
+    /// Create a builder to configure an api client
+    #[must_use]
+    pub fn builder() -> ApiClientBuilder {
+        ApiClientBuilder::new()
     }
 
     /// Create an api client using a different api endpoint
@@ -63,7 +376,7 @@ impl ApiClient {
     ///
     /// Returns an error when the provided `base_url` is not a valid url
     pub fn with_base_url(base_url: impl IntoUrl) -> Result<Self, Error> {
-        ApiClient::with_base_url_and_timeout(base_url, Duration::from_secs(15))
+        ApiClient::builder().base_url(base_url).build()
     }
 
     /// Create an api client using a different api endpoint and timeout
@@ -75,18 +388,24 @@ impl ApiClient {
         base_url: impl IntoUrl,
         timeout: Duration,
     ) -> Result<Self, Error> {
-        // ensure there is always a leading / to prevent unexpected behavior with url creation later
-        let mut base_url = base_url.into_url().map_err(|_| Error::InvalidBaseUrl)?;
-        if !base_url.path().ends_with("/") {
-            base_url.set_path(&format!("{}/", base_url.path()));
-        }
+        ApiClient::builder()
+            .base_url(base_url)
+            .timeout(timeout)
+            .build()
+    }
 
-        Ok(ApiClient {
-            base_timeout: timeout,
-            client: Client::builder().timeout(timeout).build()?,
-            base_url,
-            access_key: None,
-        })
+    /// Create an api client using an already parsed api endpoint and timeout
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the http client can't be created
+    pub fn with_url(url: Url, timeout: Duration) -> Result<Self, Error> {
+        ApiClientBuilder {
+            base_url: Ok(url),
+            ..ApiClient::builder()
+        }
+        .timeout(timeout)
+        .build()
     }
 
     /// Set access key used to access private demos
@@ -94,6 +413,195 @@ impl ApiClient {
         self.access_key = Some(access_key);
     }
 
+    /// Remove the access key, private demos will no longer be accessible
+    pub fn clear_access_key(&mut self) {
+        self.access_key = None;
+    }
+
+    /// Use an access key to access private demos
+    #[must_use]
+    pub fn with_access_key(self, access_key: impl Into<String>) -> Self {
+        ApiClient {
+            access_key: Some(access_key.into()),
+            ..self
+        }
+    }
+
+    /// Send an id generated by `generator` with every request in the `X-Request-ID` header
+    ///
+    /// The id is also recorded in the tracing span of the request, retries of a request use the same id.
+    #[must_use]
+    pub fn with_request_id_generator(
+        self,
+        generator: impl Fn() -> String + Send + Sync + 'static,
+    ) -> Self {
+        ApiClient {
+            request_id: Some(Arc::new(generator)),
+            ..self
+        }
+    }
+
+    /// Cache the results of `get` and `get_user` in memory
+    ///
+    /// The cache is shared between clones of the returned client, replacing any previously configured cache.
+    /// Demos requested with an access key are never added to the cache, since they might be private.
+    ///
+    /// The cache isn't available on wasm, where the current time can't be measured.
+    #[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+    #[must_use]
+    pub fn with_cache(self, config: CacheConfig) -> Self {
+        ApiClient {
+            cache: Some(Arc::new(Cache::new(config))),
+            ..self
+        }
+    }
+
+    /// Create a copy of the client that uses the provided options for every request
+    ///
+    /// The returned client shares the connection pool with the original client, making this cheap enough
+    /// to use for single requests.
+    #[must_use]
+    pub fn with_options(&self, options: RequestOptions) -> ApiClient {
+        ApiClient {
+            options,
+            ..self.clone()
+        }
+    }
+
+    /// The api endpoint used by the client, always ending with a `/`
+    #[must_use]
+    pub fn base_url(&self) -> &Url {
+        &self.base_url
+    }
+
+    /// Whether an access key for private demos is configured
+    #[must_use]
+    pub fn has_access_key(&self) -> bool {
+        self.access_key.is_some()
+    }
+
+    /// The number of demos the server returns for a full page
+    ///
+    /// This is discovered from the list responses and shared between clones of the client,
+    /// it returns `None` until a full page of demos has been listed.
+    /// Since the server doesn't report the page size, this is the largest number of demos in a listed page,
+    /// only pages of at least 50 demos are considered full and short pages never change the page size.
+    pub fn page_size(&self) -> Option<u32> {
+        match self.page_size.load(Ordering::Relaxed) {
+            0 => None,
+            size => Some(size),
+        }
+    }
+
+    fn request<U: IntoUrl>(&self, method: Method, url: U) -> RequestBuilder {
+        let request = self
+            .client
+            .request(method, url)
+            .header(USER_AGENT, self.user_agent.clone());
+        let request = match &self.request_id {
+            Some(generator) => request.header(REQUEST_ID_HEADER, generator()),
+            None => request,
+        };
+        #[cfg(target_arch = "wasm32")]
+        let request = request.timeout(self.options.timeout.unwrap_or(self.base_timeout));
+        match self.options.timeout {
+            Some(timeout) => request.timeout(timeout),
+            None => request,
+        }
+    }
+
+    /// Remove a demo from the cache after it has been modified
+    #[cfg_attr(
+        not(all(feature = "cache", not(target_arch = "wasm32"))),
+        allow(unused_variables)
+    )]
+    fn invalidate_demo(&self, demo_id: DemoId) {
+        #[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+        if let Some(cache) = &self.cache {
+            cache.invalidate_demo(demo_id);
+        }
+    }
+
+    /// The access key of the client, for methods that take the key from the client
+    fn stored_key(&self) -> Result<&str, Error> {
+        self.access_key.as_deref().ok_or(Error::MissingKey)
+    }
+
+    /// Add the access key header to a request if an access key is configured
+    fn with_access_key_header(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.access_key {
+            Some(access_key) => request.header(ACCESS_KEY_HEADER, access_key.as_str()),
+            None => request,
+        }
+    }
+
+    /// Send a request, waiting for the rate limit if configured
+    async fn send(&self, request: RequestBuilder) -> Result<Response, Error> {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(rate_limit) = &self.rate_limit {
+            rate_limit.acquire().await;
+        }
+        let (client, request) = request.build_split();
+        let request = request?;
+        let request_id = request
+            .headers()
+            .get(REQUEST_ID_HEADER)
+            .and_then(|id| id.to_str().ok())
+            .map(String::from);
+        match request_id {
+            Some(request_id) => {
+                timed(client.execute(request))
+                    .instrument(debug_span!("request", request_id))
+                    .await
+            }
+            None => timed(client.execute(request)).await,
+        }
+    }
+
+    /// Send a request that can safely be repeated, retrying server errors and timeouts when configured
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn send_idempotent(&self, request: RequestBuilder) -> Result<Response, Error> {
+        let Some(retry) = &self.retry else {
+            return self.send(request).await;
+        };
+
+        let mut attempt = 0;
+        loop {
+            let Some(attempt_request) = request.try_clone() else {
+                return self.send(request).await;
+            };
+            let result = self.send(attempt_request).await;
+            let retryable = match &result {
+                Ok(response) => {
+                    response.status().is_server_error()
+                        || response.status() == StatusCode::TOO_MANY_REQUESTS
+                }
+                Err(e) => matches!(e, Error::TimeOut),
+            };
+            if !retryable || attempt >= retry.max_retries {
+                return result;
+            }
+
+            // when rate limited, wait as long as the server tells us to,
+            // if that's longer than we're willing to wait, let the caller handle the rate limit instead
+            let delay = match result.as_ref().ok().and_then(retry_after) {
+                Some(retry_after) if retry_after > retry.max_delay => return result,
+                Some(retry_after) => retry_after,
+                None => retry.delay(attempt),
+            };
+            debug!(attempt, delay = debug(delay), "retrying request");
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Send a request that can safely be repeated, requests are never retried on wasm
+    #[cfg(target_arch = "wasm32")]
+    async fn send_idempotent(&self, request: RequestBuilder) -> Result<Response, Error> {
+        self.send(request).await
+    }
+
+    /// Build the url for an endpoint, `path` must not start with a `/` to keep any path of the base url
     fn url<P: AsRef<str>>(&self, path: P) -> Result<Url, Error> {
         self.base_url
             .join(path.as_ref())
@@ -120,6 +628,9 @@ impl ApiClient {
     ///
     /// note that the pages start counting at 1
     ///
+    /// The duration and name filters of `ListParams` are applied after the page is received, so a page can
+    /// contain fewer demos than a full page, or none at all, even if more pages are available.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -138,15 +649,19 @@ impl ApiClient {
     /// # Ok(())
     /// # }
     /// ```
-    #[instrument]
+    #[instrument(skip(self))]
     pub async fn list(&self, params: ListParams, page: u32) -> Result<Vec<Demo>, Error> {
-        self.list_url(self.url("demos")?, params, page).await
+        Ok(self.list_page(params, page).await?.demos)
     }
 
-    /// List demos uploaded by a user with the provided options
+    /// List a page of demos with the provided options, including whether more pages are available
     ///
     /// note that the pages start counting at 1
     ///
+    /// The duration and name filters of `ListParams` are applied after the page is received, while `has_more`
+    /// is based on the unfiltered page. A page can thus contain fewer demos than a full page, or none at all,
+    /// while `has_more` is `true`.
+    ///
     /// # Example
     ///
     /// ```rust
@@ -155,333 +670,3213 @@ impl ApiClient {
     ///
     /// # #[tokio::main]
     /// # async fn main() -> Result<(), demostf_client::Error> {
-    /// # use steamid_ng::SteamID;
-    /// let client = ApiClient::default();
+    /// # let client = ApiClient::default();
     /// #
-    /// let demos = client.list_uploads(SteamID::from(76561198024494988), ListParams::default().with_order(ListOrder::Ascending), 1).await?;
+    /// let page = client.list_page(ListParams::default().with_order(ListOrder::Ascending), 1).await?;
     ///
-    /// for demo in demos {
+    /// for demo in page.demos {
     ///     println!("{}: {}", demo.id, demo.name);
     /// }
+    /// if page.has_more {
+    ///     println!("next page: {}", page.page + 1);
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    #[instrument]
-    pub async fn list_uploads(
-        &self,
-        uploader: SteamID,
-        params: ListParams,
-        page: u32,
-    ) -> Result<Vec<Demo>, Error> {
-        self.list_url(
-            self.url(format!("uploads/{}", u64::from(uploader)))?,
-            params,
+    #[instrument(skip(self))]
+    pub async fn list_page(&self, params: ListParams, page: u32) -> Result<DemoPage, Error> {
+        let mut demos = self.list_url(self.url("demos")?, &params, page).await?;
+        let has_more = self.is_full_page(demos.len());
+        params.retain_matching(&mut demos);
+        Ok(DemoPage {
+            has_more,
+            demos,
             page,
-        )
-        .await
-    }
-
-    async fn list_url(&self, url: Url, params: ListParams, page: u32) -> Result<Vec<Demo>, Error> {
-        if page == 0 {
-            return Err(Error::InvalidPage);
-        }
-
-        let mut req = self.client.get(url);
-
-        if let Some(access_key) = &self.access_key {
-            req = req.header("ACCESS_KEY", access_key.as_str());
-        }
-
-        Ok(req
-            .query(&[("page", page)])
-            .query(&params)
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?)
+        })
     }
 
-    /// Get the data for a single demo
+    /// List demos with the provided options, returning the unprocessed json data for every demo
+    ///
+    /// This allows accessing fields returned by the api that aren't supported by [`Demo`] yet.
+    ///
+    /// note that the pages start counting at 1
+    ///
+    /// The duration and name filters of `ListParams` are applied after the page is received, so a page can
+    /// contain fewer demos than a full page, or none at all, even if more pages are available.
     ///
     /// # Example
     ///
     /// ```rust
+    /// use demostf_client::ListParams;
     /// # use demostf_client::ApiClient;
-    /// #
+    ///
     /// # #[tokio::main]
     /// # async fn main() -> Result<(), demostf_client::Error> {
     /// # let client = ApiClient::default();
     /// #
-    /// let demo = client.get(9).await?;
-    ///
-    /// println!("{}: {}", demo.id, demo.name);
-    /// println!("players:");
+    /// let demos = client.list_raw(ListParams::default(), 1).await?;
     ///
-    /// for player in demo.players.unwrap_or_default() {
-    ///     println!("{}", player.user.name);
+    /// for demo in demos {
+    ///     println!("{}: {}", demo["id"], demo["name"]);
     /// }
     /// # Ok(())
     /// # }
     /// ```
-    #[instrument]
-    pub async fn get(&self, demo_id: u32) -> Result<Demo, Error> {
-        let mut req = self.client.get(self.url(format!("/demos/{}", demo_id))?);
-
-        if let Some(access_key) = &self.access_key {
-            req = req.header("ACCESS-KEY", access_key.as_str());
-        }
-
-        let response = req.send().await?;
-
-        if response.status() == StatusCode::NOT_FOUND {
-            return Err(Error::DemoNotFound(demo_id));
-        }
-
-        Ok(response.error_for_status()?.json().await?)
+    #[instrument(skip(self))]
+    pub async fn list_raw(&self, params: ListParams, page: u32) -> Result<Vec<Value>, Error> {
+        let mut demos = self.list_url(self.url("demos")?, &params, page).await?;
+        params.retain_matching_raw(&mut demos);
+        Ok(demos)
     }
 
-    /// Get user info by id
+    /// List all demos matching the provided options, automatically requesting new pages as needed
+    ///
+    /// The stream ends after the last page has been read or after the first error
     ///
     /// # Example
     ///
     /// ```rust
+    /// use demostf_client::{ListOrder, ListParams};
+    /// use futures_util::{StreamExt, TryStreamExt};
     /// # use demostf_client::ApiClient;
-    /// #
+    ///
     /// # #[tokio::main]
     /// # async fn main() -> Result<(), demostf_client::Error> {
     /// # let client = ApiClient::default();
     /// #
-    /// let user = client.get_user(1).await?;
+    /// let demos: Vec<_> = client
+    ///     .list_stream(ListParams::default().with_order(ListOrder::Ascending))
+    ///     .take(500)
+    ///     .try_collect()
+    ///     .await?;
     ///
-    /// println!("{} ({})", user.name, user.steam_id.steam3());
+    /// for demo in demos {
+    ///     println!("{}: {}", demo.id, demo.name);
+    /// }
     /// # Ok(())
     /// # }
     /// ```
-    #[instrument]
-    pub async fn get_user(&self, user_id: u32) -> Result<User, Error> {
-        let response = self
-            .client
-            .get(self.url(format!("/users/{}", user_id))?)
-            .send()
-            .await?;
-
-        if response.status() == StatusCode::NOT_FOUND {
-            return Err(Error::UserNotFound(user_id));
-        }
-
-        Ok(response.error_for_status()?.json().await?)
+    pub fn list_stream(&self, params: ListParams) -> impl Stream<Item = Result<Demo, Error>> + '_ {
+        self.paginate("demos".into(), params)
     }
 
-    /// Search for players by name
+    /// List all demos with an id higher than `after_id` matching the provided options, in ascending id order
+    ///
+    /// The demo with id `after_id` itself is not included, so passing the highest id that has been processed
+    /// yields exactly the demos that haven't been seen yet. Any order or after id set in `params` is replaced.
     ///
     /// # Example
     ///
     /// ```rust
+    /// use demostf_client::ListParams;
+    /// use futures_util::TryStreamExt;
     /// # use demostf_client::ApiClient;
-    /// #
+    ///
     /// # #[tokio::main]
     /// # async fn main() -> Result<(), demostf_client::Error> {
-    /// let client = ApiClient::default();
+    /// # let client = ApiClient::default();
     /// #
-    /// let users = client.search_users("icewind").await?;
+    /// let mut last_seen = 1_000_000;
+    /// let mut demos = Box::pin(client.list_since(last_seen, ListParams::default()));
     ///
-    /// for user in users {
-    ///   println!("{} ({})", user.name, user.steam_id.steam3());
+    /// while let Some(demo) = demos.try_next().await? {
+    ///     println!("{}: {}", demo.id, demo.name);
+    ///     last_seen = demo.id.0.into();
     /// }
     /// # Ok(())
     /// # }
     /// ```
-    #[instrument]
-    pub async fn search_users(&self, name: &str) -> Result<Vec<User>, Error> {
-        let response = self
-            .client
-            .get(self.url_with_params("/users/search", [("query", name)])?)
-            .send()
-            .await?;
+    pub fn list_since(
+        &self,
+        after_id: u64,
+        params: ListParams,
+    ) -> impl Stream<Item = Result<Demo, Error>> + '_ {
+        self.list_stream(
+            params
+                .with_after_id(after_id)
+                .with_order(ListOrder::Ascending),
+        )
+    }
 
-        Ok(response.error_for_status()?.json().await?)
+    /// List demos uploaded by a user with the provided options
+    ///
+    /// note that the pages start counting at 1
+    ///
+    /// Users unknown to the api are treated as having no uploads
+    ///
+    /// The duration and name filters of `ListParams` are applied after the page is received, so a page can
+    /// contain fewer demos than a full page, or none at all, even if more pages are available.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use demostf_client::{ListOrder, ListParams};
+    /// # use demostf_client::ApiClient;
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # use steamid_ng::SteamID;
+    /// let client = ApiClient::default();
+    /// #
+    /// let demos = client.list_uploads(SteamID::from(76561198024494988), ListParams::default().with_order(ListOrder::Ascending), 1).await?;
+    ///
+    /// for demo in demos {
+    ///     println!("{}: {}", demo.id, demo.name);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, uploader), fields(uploader = u64::from(uploader)))]
+    pub async fn list_uploads(
+        &self,
+        uploader: SteamID,
+        params: ListParams,
+        page: u32,
+    ) -> Result<Vec<Demo>, Error> {
+        let mut demos = match self
+            .list_url(
+                self.url(format!("uploads/{}", u64::from(uploader)))?,
+                &params,
+                page,
+            )
+            .await
+        {
+            Err(e) if e.is_not_found() => {
+                debug!("uploader not found, no uploads");
+                Vec::new()
+            }
+            result => result?,
+        };
+        params.retain_matching(&mut demos);
+        Ok(demos)
     }
 
-    /// List demos with the provided options
+    /// Get the number of demos uploaded by a user
+    ///
+    /// The api doesn't provide the number of uploads directly, instead the last page of uploads is located by
+    /// requesting exponentially further pages followed by a binary search. This requires a number of requests
+    /// logarithmic in the number of uploads.
+    ///
+    /// The count is exact as long as no demos are uploaded while counting and the server returns at least
+    /// 50 demos for a full page, pages are only considered full once they reach that size.
     ///
     /// # Example
     ///
     /// ```rust
     /// # use demostf_client::ApiClient;
+    /// # use steamid_ng::SteamID;
     /// #
     /// # #[tokio::main]
     /// # async fn main() -> Result<(), demostf_client::Error> {
     /// # let client = ApiClient::default();
     /// #
-    /// let chat = client.get_chat(447678).await?;
-    ///
-    /// for message in chat {
-    ///     println!("{}: {}", message.user, message.message);
-    /// }
+    /// let count = client.upload_count(SteamID::from(76561198024494988)).await?;
+    /// println!("{} demos uploaded", count);
     /// # Ok(())
     /// # }
     /// ```
-    #[instrument]
-    pub async fn get_chat(&self, demo_id: u32) -> Result<Vec<ChatMessage>, Error> {
-        let response = self
-            .client
-            .get(self.url(format!("/demos/{}/chat", demo_id))?)
-            .send()
-            .await?;
+    #[instrument(skip(self, uploader), fields(uploader = u64::from(uploader)))]
+    pub async fn upload_count(&self, uploader: SteamID) -> Result<u64, Error> {
+        let url = self.url(format!("uploads/{}", u64::from(uploader)))?;
+        let params = ListParams::default();
+        let page_len = |page: u32| {
+            let url = url.clone();
+            let params = &params;
+            async move {
+                let demos: Vec<IgnoredAny> = self.list_url(url, params, page).await?;
+                Ok::<_, Error>(demos.len())
+            }
+        };
 
-        if response.status() == StatusCode::NOT_FOUND {
-            return Err(Error::DemoNotFound(demo_id));
+        let first = page_len(1).await?;
+        if !self.is_full_page(first) {
+            return Ok(first as u64);
+        }
+        let page_size = self.full_page_size();
+        let count =
+            |full_pages: u32, rest: usize| u64::from(full_pages) * page_size as u64 + rest as u64;
+
+        // find a page past the last full page
+        let (mut full, mut past) = (1, 2);
+        loop {
+            match page_len(past).await? {
+                len if self.is_full_page(len) && past < MAX_PAGE => {
+                    full = past;
+                    past = past.saturating_mul(2).min(MAX_PAGE);
+                }
+                0 => break,
+                len => return Ok(count(past - 1, len)),
+            }
         }
 
-        Ok(response.error_for_status()?.json().await?)
+        // binary search for the last page, `full` is always a full page and `past` always empty
+        while past - full > 1 {
+            let page = full + (past - full) / 2;
+            match page_len(page).await? {
+                len if self.is_full_page(len) => full = page,
+                0 => past = page,
+                len => return Ok(count(page - 1, len)),
+            }
+        }
+        Ok(count(full, 0))
     }
 
-    #[instrument]
-    pub async fn set_url(
+    /// Request consecutive pages from a list endpoint until a page comes back short or empty
+    fn paginate(
         &self,
-        demo_id: u32,
-        backend: &str,
-        path: &str,
-        url: &str,
-        hash: [u8; 16],
-        key: &str,
-    ) -> Result<(), Error> {
+        path: String,
+        params: ListParams,
+    ) -> impl Stream<Item = Result<Demo, Error>> + '_ {
+        let start = PageCursor { params, page: 1 };
+        stream::unfold(Some(start), move |cursor| {
+            let path = path.clone();
+            async move {
+                let cursor = cursor?;
+                let result = match self.url(path) {
+                    Ok(url) => self.list_url(url, &cursor.params, cursor.page).await,
+                    Err(e) => Err(e),
+                };
+                match result {
+                    Ok(demos) if demos.is_empty() => None,
+                    Ok(mut demos) => {
+                        let full = self.is_full_page(demos.len());
+                        cursor.params.retain_matching(&mut demos);
+                        let next = full.then_some(PageCursor {
+                            params: cursor.params,
+                            page: cursor.page + 1,
+                        });
+                        Some((Ok(demos), next))
+                    }
+                    Err(e) => Some((Err(e), None)),
+                }
+            }
+        })
+        .flat_map(|page| {
+            stream::iter(match page {
+                Ok(demos) => demos.into_iter().map(Ok).collect(),
+                Err(e) => vec![Err(e)],
+            })
+        })
+    }
+
+    async fn list_url<T: DeserializeOwned>(
+        &self,
+        url: Url,
+        params: &ListParams,
+        page: u32,
+    ) -> Result<Vec<T>, Error> {
+        validate_page(page)?;
+
+        let req = self.with_access_key_header(self.request(Method::GET, url));
+
         let response = self
-            .client
-            .post(self.url(format!("/demos/{}/url", demo_id))?)
-            .form(&[
-                ("hash", hex::encode(hash).as_str()),
-                ("backend", backend),
-                ("url", url),
-                ("path", path),
-                ("key", key),
-            ])
-            .send()
+            .send_idempotent(req.query(&[("page", page)]).query(params))
             .await?;
+        let demos: Vec<T> = parse_json(error_for_status(response).await?).await?;
+        if self.is_full_page(demos.len()) {
+            // short pages are only the end of a list, full pages tell us how large a page can get
+            self.page_size
+                .fetch_max(demos.len() as u32, Ordering::Relaxed);
+        }
+        Ok(demos)
+    }
 
-        if response.status() == StatusCode::NOT_FOUND {
-            return Err(Error::DemoNotFound(demo_id));
+    /// The number of demos in a full page, `PAGE_SIZE` until a full page has been seen
+    fn full_page_size(&self) -> usize {
+        match self.page_size.load(Ordering::Relaxed) {
+            0 => PAGE_SIZE,
+            size => size as usize,
         }
+    }
 
-        response.error_for_status()?;
+    /// Whether a page with `len` demos is a full page, meaning more pages might be available
+    fn is_full_page(&self, len: usize) -> bool {
+        len > 0 && len >= self.full_page_size()
+    }
 
-        Ok(())
+    /// Get the data for a single demo
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let demo = client.get(9).await?;
+    ///
+    /// println!("{}: {}", demo.id, demo.name);
+    /// println!("players:");
+    ///
+    /// for player in demo.players.unwrap_or_default() {
+    ///     println!("{}", player.user.name);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, demo_id), fields(demo_id))]
+    pub async fn get(&self, demo_id: impl Into<DemoId>) -> Result<Demo, Error> {
+        let demo_id = demo_id.into();
+        Span::current().record("demo_id", demo_id.0);
+
+        #[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+        if let Some(demo) = self.cache.as_ref().and_then(|cache| cache.demo(demo_id)) {
+            return Ok(demo);
+        }
+
+        let demo: Demo = parse_json(self.demo_response(Method::GET, demo_id).await?).await?;
+
+        // demos requested with an access key might be private, caching them would leak them to
+        // clones of the client without the key that share the cache
+        #[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+        if let (Some(cache), None) = (&self.cache, &self.access_key) {
+            cache.insert_demo(demo.clone());
+        }
+
+        Ok(demo)
     }
 
-    #[instrument(skip(body))]
-    pub async fn upload_demo(
-        &self,
-        file_name: String,
-        body: Vec<u8>,
-        red: String,
-        blue: String,
-        key: String,
-    ) -> Result<u32, Error> {
-        self.upload_maybe_private_demo(file_name, body, red, blue, key, false)
-            .await
+    /// Get the data for a demo from a link to the demo, such as `https://demos.tf/447678`
+    ///
+    /// The demo id is taken from the last segment of the path, ignoring any query string or trailing `/`.
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidDemoUrl` if the url doesn't end in a demo id
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let demo = client.get_from_url("https://demos.tf/9").await?;
+    ///
+    /// println!("{}: {}", demo.id, demo.name);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_from_url(&self, url: &str) -> Result<Demo, Error> {
+        let demo_id = demo_id_from_url(url).ok_or_else(|| Error::InvalidDemoUrl(url.into()))?;
+        self.get(demo_id).await
     }
 
-    #[instrument(skip(body))]
-    pub async fn upload_private_demo(
-        &self,
-        file_name: String,
-        body: Vec<u8>,
-        red: String,
-        blue: String,
-        key: String,
-    ) -> Result<u32, Error> {
-        self.upload_maybe_private_demo(file_name, body, red, blue, key, true)
-            .await
+    /// Get the unprocessed json data for a single demo
+    ///
+    /// This allows accessing fields returned by the api that aren't supported by [`Demo`] yet.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let demo = client.get_raw(9).await?;
+    ///
+    /// println!("{}", demo["name"]);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, demo_id), fields(demo_id))]
+    pub async fn get_raw(&self, demo_id: impl Into<DemoId>) -> Result<Value, Error> {
+        let demo_id = demo_id.into();
+        Span::current().record("demo_id", demo_id.0);
+
+        parse_json(self.demo_response(Method::GET, demo_id).await?).await
     }
 
-    async fn upload_maybe_private_demo(
-        &self,
-        file_name: String,
-        body: Vec<u8>,
-        red: String,
-        blue: String,
-        key: String,
-        private: bool,
-    ) -> Result<u32, Error> {
-        let form = multipart::Form::new()
-            .text("red", red)
-            .text("blue", blue)
-            .text("name", file_name)
-            .text("key", key)
-            .text("private", if private { "1" } else { "0" });
+    async fn demo_response(&self, method: Method, demo_id: DemoId) -> Result<Response, Error> {
+        let req = self
+            .with_access_key_header(self.request(method, self.url(format!("demos/{}", demo_id))?));
 
-        let file = multipart::Part::bytes(body)
-            .file_name("demo.dem")
-            .mime_str("text/plain")?;
+        let response = self.send_idempotent(req).await?;
 
-        let form = form.part("demo", file);
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::DemoNotFound(demo_id));
+        }
 
-        let resp = self
-            .client
-            .post(self.url("/upload")?)
-            .multipart(form)
-            .send()
-            .await?
-            .error_for_status()?
-            .text()
-            .await?;
+        error_for_status(response).await
+    }
+
+    /// Check that the api is reachable by requesting the root of the api
+    ///
+    /// The request uses a timeout of at most [`ApiClient::PING_TIMEOUT`] and isn't retried.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// client.ping().await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn ping(&self) -> Result<(), Error> {
+        let timeout = self
+            .options
+            .timeout
+            .unwrap_or(self.base_timeout)
+            .min(Self::PING_TIMEOUT);
+        let request = self.request(Method::GET, self.url("")?).timeout(timeout);
+        let response = self.send(request).await?;
+        error_for_status(response).await?;
+        Ok(())
+    }
+
+    /// Check if a demo exists without downloading the demo data
+    ///
+    /// Servers that don't allow `HEAD` requests are handled by falling back to a normal request.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// if client.exists(9).await? {
+    ///     println!("demo 9 exists");
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, demo_id), fields(demo_id))]
+    pub async fn exists(&self, demo_id: impl Into<DemoId>) -> Result<bool, Error> {
+        let demo_id = demo_id.into();
+        Span::current().record("demo_id", demo_id.0);
 
-        if resp == "Invalid key" {
-            return Err(Error::InvalidApiKey);
+        let result = match self.demo_response(Method::HEAD, demo_id).await {
+            Err(Error::Request(e)) if e.status() == Some(StatusCode::METHOD_NOT_ALLOWED) => {
+                self.demo_response(Method::GET, demo_id).await
+            }
+            result => result,
+        };
+        match result {
+            Ok(_) => Ok(true),
+            Err(Error::DemoNotFound(_)) => Ok(false),
+            Err(e) => Err(e),
         }
+    }
+
+    /// Get the data for multiple demos, requesting up to `concurrency` demos at the same time
+    ///
+    /// Results are yielded as soon as they are available and thus not necessarily in the same order as the
+    /// provided ids. A failure to get one demo does not affect the other demos.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// use futures_util::StreamExt;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let mut demos = client.get_many([9, 10, 11], 2);
+    ///
+    /// while let Some((id, demo)) = demos.next().await {
+    ///     match demo {
+    ///         Ok(demo) => println!("{}: {}", id, demo.name),
+    ///         Err(e) => println!("{}: {}", id, e),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get_many(
+        &self,
+        ids: impl IntoIterator<Item = impl Into<DemoId>>,
+        concurrency: usize,
+    ) -> impl Stream<Item = (DemoId, Result<Demo, Error>)> + '_ {
+        let ids: Vec<DemoId> = ids.into_iter().map(Into::into).collect();
 
-        let tail = resp.split('/').last().unwrap_or_default();
-        u32::from_str(tail).map_err(|_| Error::InvalidResponse(resp))
+        stream::iter(ids)
+            .map(move |id| async move { (id, self.get(id).await) })
+            .buffer_unordered(concurrency.max(1))
     }
 
-    pub(crate) async fn download_demo(&self, url: &str, duration: u16) -> Result<Response, Error> {
-        // set timeout to 1s per 60s (~1mb) with a minimum of 15s, scaled by an configured timeout (default 15s)
-        let timeout_scale = (f32::from(duration) / 60.0).max(15.0) / 15.0;
-        let timeout = Duration::from_secs_f32(self.base_timeout.as_secs_f32() * timeout_scale);
-        trace!(url = url, timeout = debug(timeout), "requesting demo file");
-        Ok(self
-            .client
-            .get(url)
-            .timeout(timeout)
-            .send()
-            .await?
-            .error_for_status()?)
+    /// Download multiple demos into a directory, downloading up to `concurrency` demos at the same time
+    ///
+    /// Every demo is saved as `{id}_{name}` with the hash verified as in [`Demo::save`], using
+    /// [`Demo::safe_filename`] for the name. Results are yielded as soon as a download is
+    /// finished, a failed download doesn't affect the other demos and doesn't leave a partial file behind.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use demostf_client::{ApiClient, ListParams};
+    /// use futures_util::StreamExt;
+    /// use std::path::Path;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let demos = client.list(ListParams::default(), 1).await?;
+    /// let mut downloads = client.download_all(&demos, Path::new("demos"), 4);
+    ///
+    /// while let Some((id, result)) = downloads.next().await {
+    ///     match result {
+    ///         Ok(path) => println!("{}: saved to {}", id, path.display()),
+    ///         Err(e) => println!("{}: {}", id, e),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn download_all<'a>(
+        &'a self,
+        demos: &'a [Demo],
+        dir: &'a Path,
+        concurrency: usize,
+    ) -> impl Stream<Item = (DemoId, Result<PathBuf, Error>)> + 'a {
+        stream::iter(demos)
+            .map(move |demo| async move {
+                let path = dir.join(format!("{}_{}", demo.id, demo.safe_filename()));
+                (demo.id, self.download_to_file(demo, path).await)
+            })
+            .buffer_unordered(concurrency.max(1))
     }
-}
 
-#[test]
-fn test_url() {
+    /// Pair every demo of a stream with the user info of its uploader
+    ///
+    /// Demos are handled in batches of the demos that are available, the uploaders of a batch are requested with
+    /// the concurrency of [`ApiClient::get_users`] and every uploader is only requested once for the entire stream.
+    /// The stream ends after the first error, either from the provided stream or from requesting an uploader.
+    /// When requesting the uploaders of a batch fails, the demos of the batch with an already known uploader are
+    /// still returned and the error takes the place of the first demo with an unknown uploader.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::{ApiClient, ListParams};
+    /// use futures_util::{StreamExt, TryStreamExt};
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let demos = client.list_stream(ListParams::default()).take(100);
+    /// let demos: Vec<_> = client.resolve_uploaders(demos).try_collect().await?;
+    ///
+    /// for (demo, uploader) in demos {
+    ///     println!("{}: uploaded by {}", demo.id, uploader.name);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn resolve_uploaders<'a>(
+        &'a self,
+        demos: impl Stream<Item = Result<Demo, Error>> + 'a,
+    ) -> impl Stream<Item = Result<(Demo, User), Error>> + 'a {
+        let batches = Box::pin(demos.ready_chunks(PAGE_SIZE));
+        stream::unfold(Some((batches, HashMap::new())), move |state| async move {
+            let (mut batches, mut users) = state?;
+            let batch = batches.next().await?;
+            let (results, failed) = self.resolve_batch(batch, &mut users).await;
+            Some((stream::iter(results), (!failed).then_some((batches, users))))
+        })
+        .flatten()
+    }
+
+    /// Resolve the uploaders of a batch of demos, returning the results up to the first error
+    ///
+    /// If the missing uploaders can't be requested, only the demos with a known uploader are resolved.
+    async fn resolve_batch(
+        &self,
+        batch: Vec<Result<Demo, Error>>,
+        users: &mut HashMap<UserId, User>,
+    ) -> (Vec<Result<(Demo, User), Error>>, bool) {
+        let mut demos = Vec::with_capacity(batch.len());
+        let mut error = None;
+        for demo in batch {
+            match demo {
+                Ok(demo) => demos.push(demo),
+                Err(e) => {
+                    error = Some(e);
+                    break;
+                }
+            }
+        }
+
+        for user in demos.iter().filter_map(|demo| demo.uploader.user()) {
+            users.entry(user.id).or_insert_with(|| user.clone());
+        }
+        let missing: BTreeSet<UserId> = demos
+            .iter()
+            .map(|demo| demo.uploader.id())
+            .filter(|id| !users.contains_key(id))
+            .collect();
+        let mut fetch_error = match self.get_users(missing).await {
+            Ok(fetched) => {
+                users.extend(fetched.into_iter().map(|user| (user.id, user)));
+                None
+            }
+            Err(e) => Some(e),
+        };
+        let fetch_failed = fetch_error.is_some();
+
+        let mut results = Vec::with_capacity(demos.len() + 1);
+        for demo in demos {
+            let id = demo.uploader.id();
+            match users.get(&id) {
+                Some(user) => results.push(Ok((demo, user.clone()))),
+                // the error is only reported once, for the first demo that couldn't be resolved
+                None if fetch_failed => results.extend(fetch_error.take().map(Err)),
+                None => results.push(Err(Error::UserNotFound(id))),
+            }
+        }
+        let failed = fetch_failed || error.is_some();
+        results.extend(error.map(Err));
+        (results, failed)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn download_to_file(&self, demo: &Demo, path: PathBuf) -> Result<PathBuf, Error> {
+        let file = File::create(&path).await.map_err(Error::Write)?;
+        let mut file = BufWriter::new(file);
+        let result = match demo.save_async(self, &mut file).await {
+            Ok(()) => file.flush().await.map_err(Error::Write),
+            Err(e) => Err(e),
+        };
+        drop(file);
+        match result {
+            Ok(()) => Ok(path),
+            Err(e) => {
+                let _ = tokio::fs::remove_file(&path).await;
+                Err(e)
+            }
+        }
+    }
+
+    /// Get user info by id
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let user = client.get_user(1).await?;
+    ///
+    /// println!("{} ({})", user.name, user.steam_id.steam3());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, user_id), fields(user_id))]
+    pub async fn get_user(&self, user_id: impl Into<UserId>) -> Result<User, Error> {
+        let user_id = user_id.into();
+        Span::current().record("user_id", user_id.0);
+
+        #[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+        if let Some(user) = self.cache.as_ref().and_then(|cache| cache.user(user_id)) {
+            return Ok(user);
+        }
+
+        let response = self
+            .send_idempotent(self.request(Method::GET, self.url(format!("users/{}", user_id))?))
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::UserNotFound(user_id));
+        }
+
+        let user: User = parse_json(error_for_status(response).await?).await?;
+
+        #[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+        if let Some(cache) = &self.cache {
+            cache.insert_user(user.clone());
+        }
+
+        Ok(user)
+    }
+
+    /// Get user info for multiple users, in the same order as the provided ids
+    ///
+    /// Since there is no api endpoint for getting multiple users at once, the users are requested
+    /// individually with up to [`ApiClient::USER_CONCURRENCY`] requests at the same time.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let users = client.get_users([1, 2, 3]).await?;
+    ///
+    /// for user in users {
+    ///     println!("{} ({})", user.name, user.steam_id.steam3());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn get_users(
+        &self,
+        ids: impl IntoIterator<Item = impl Into<UserId>>,
+    ) -> Result<Vec<User>, Error> {
+        stream::iter(ids.into_iter().map(Into::into))
+            .map(|id: UserId| self.get_user(id))
+            .buffered(Self::USER_CONCURRENCY)
+            .try_collect()
+            .await
+    }
+
+    /// Search for players by name
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// let client = ApiClient::default();
+    /// #
+    /// let users = client.search_users("icewind").await?;
+    ///
+    /// for user in users {
+    ///   println!("{} ({})", user.name, user.steam_id.steam3());
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn search_users(&self, name: &str) -> Result<Vec<User>, Error> {
+        let response = self
+            .send_idempotent(self.request(
+                Method::GET,
+                self.url_with_params("users/search", [("query", name)])?,
+            ))
+            .await?;
+
+        parse_json(error_for_status(response).await?).await
+    }
+
+    /// Search for players by name, returning at most `limit` users from the requested page
+    ///
+    /// Pages start counting at 1. If the server doesn't support paging the search results and
+    /// returns more than `limit` users, the requested page is taken from the full result list instead.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// let client = ApiClient::default();
+    /// #
+    /// let users = client.search_users_paged("john", 1, 10).await?;
+    /// assert!(users.len() <= 10);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self))]
+    pub async fn search_users_paged(
+        &self,
+        name: &str,
+        page: u32,
+        limit: u32,
+    ) -> Result<Vec<User>, Error> {
+        validate_user_page(page, limit)?;
+        let page_param = page.to_string();
+        let limit_param = limit.to_string();
+        let response = self
+            .send_idempotent(self.request(
+                Method::GET,
+                self.url_with_params(
+                    "users/search",
+                    [
+                        ("query", name),
+                        ("page", page_param.as_str()),
+                        ("limit", limit_param.as_str()),
+                    ],
+                )?,
+            ))
+            .await?;
+
+        let mut users: Vec<User> = parse_json(error_for_status(response).await?).await?;
+        let limit = limit as usize;
+        if users.len() > limit {
+            // the server ignored the paging parameters
+            let start = (page as usize - 1).saturating_mul(limit);
+            users = users.into_iter().skip(start).take(limit).collect();
+        }
+        Ok(users)
+    }
+
+    /// List demos with the provided options
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let chat = client.get_chat(447678).await?;
+    ///
+    /// for message in chat {
+    ///     println!("{}: {}", message.user, message.message);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, demo_id), fields(demo_id))]
+    pub async fn get_chat(&self, demo_id: impl Into<DemoId>) -> Result<Vec<ChatMessage>, Error> {
+        let demo_id = demo_id.into();
+        Span::current().record("demo_id", demo_id.0);
+
+        parse_json(self.chat_response(demo_id).await?).await
+    }
+
+    /// Get the chat messages send between `start` and `end` (inclusive), in seconds since the start of the demo
+    ///
+    /// If `start` is after `end` no messages are returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let chat = client.get_chat_between(447678, 60, 120).await?;
+    ///
+    /// for message in chat {
+    ///     println!("{}: {}", message.user, message.message);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, demo_id), fields(demo_id))]
+    pub async fn get_chat_between(
+        &self,
+        demo_id: impl Into<DemoId>,
+        start: u32,
+        end: u32,
+    ) -> Result<Vec<ChatMessage>, Error> {
+        let demo_id = demo_id.into();
+        Span::current().record("demo_id", demo_id.0);
+
+        if start > end {
+            return Ok(Vec::new());
+        }
+
+        let mut chat = self.get_chat(demo_id).await?;
+        chat.retain(|message| (start..=end).contains(&message.time));
+        Ok(chat)
+    }
+
+    /// Get the chat messages for a demo, parsing the messages as they are received
+    ///
+    /// This avoids having to load the full chat of long demos in memory at once.
+    /// If the demo doesn't exist the stream yields a single `Error::DemoNotFound`.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// use futures_util::StreamExt;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let mut chat = client.chat_stream(447678);
+    ///
+    /// while let Some(message) = chat.next().await {
+    ///     let message = message?;
+    ///     println!("{}: {}", message.user, message.message);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn chat_stream(
+        &self,
+        demo_id: impl Into<DemoId>,
+    ) -> impl Stream<Item = Result<ChatMessage, Error>> + '_ {
+        let demo_id = demo_id.into();
+        stream::unfold(ChatState::Start, move |state| async move {
+            let (mut body, mut parser, mut pending) = match state {
+                ChatState::Start => match self.chat_response(demo_id).await {
+                    Ok(response) => (
+                        body_stream(response),
+                        JsonArrayParser::default(),
+                        VecDeque::new(),
+                    ),
+                    Err(e) => return Some((Err(e), ChatState::Done)),
+                },
+                ChatState::Reading {
+                    body,
+                    parser,
+                    pending,
+                } => (body, parser, pending),
+                ChatState::Done => return None,
+            };
+
+            loop {
+                if let Some(message) = pending.pop_front() {
+                    let state = ChatState::Reading {
+                        body,
+                        parser,
+                        pending,
+                    };
+                    return Some((Ok(message), state));
+                }
+
+                match body.next().await.transpose() {
+                    Ok(Some(chunk)) => match parser.feed(&chunk) {
+                        Ok(messages) => pending.extend(messages),
+                        Err(e) => return Some((Err(e), ChatState::Done)),
+                    },
+                    Ok(None) if parser.is_finished() => return None,
+                    Ok(None) => {
+                        let err = Error::InvalidResponse("incomplete chat data".into());
+                        return Some((Err(err), ChatState::Done));
+                    }
+                    Err(e) => return Some((Err(e.into()), ChatState::Done)),
+                }
+            }
+        })
+    }
+
+    async fn chat_response(&self, demo_id: DemoId) -> Result<Response, Error> {
+        let response = self
+            .send_idempotent(self.with_access_key_header(
+                self.request(Method::GET, self.url(format!("demos/{}/chat", demo_id))?),
+            ))
+            .await?;
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::DemoNotFound(demo_id));
+        }
+
+        error_for_status(response).await
+    }
+
+    #[instrument(skip(self, demo_id, key), fields(demo_id))]
+    pub async fn set_url(
+        &self,
+        demo_id: impl Into<DemoId>,
+        backend: &str,
+        path: &str,
+        url: &str,
+        hash: [u8; 16],
+        key: &str,
+    ) -> Result<(), Error> {
+        let demo_id = demo_id.into();
+        Span::current().record("demo_id", demo_id.0);
+
+        let request = self
+            .request(Method::POST, self.url(format!("demos/{}/url", demo_id))?)
+            .form(&[
+                ("hash", hex::encode(hash).as_str()),
+                ("backend", backend),
+                ("url", url),
+                ("path", path),
+                ("key", key),
+            ]);
+        let response = self.send(request).await?;
+        self.invalidate_demo(demo_id);
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::DemoNotFound(demo_id));
+        }
+        if response.status() == StatusCode::PRECONDITION_FAILED {
+            return Err(Error::HashMisMatch {
+                expected: hash,
+                calculated: None,
+            });
+        }
+
+        error_for_status(response).await?;
+
+        Ok(())
+    }
+
+    /// Set the url of a demo, using the md5 hash of a local copy of the demo file
+    ///
+    /// Calculating the hash locally makes sure the file at the new url matches the demo before the server
+    /// is asked to verify it.
+    #[instrument(skip(self, demo_id, key), fields(demo_id))]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub async fn set_url_from_file(
+        &self,
+        demo_id: impl Into<DemoId>,
+        backend: &str,
+        path: &str,
+        url: &str,
+        file: &Path,
+        key: &str,
+    ) -> Result<(), Error> {
+        let demo_id = demo_id.into();
+        Span::current().record("demo_id", demo_id.0);
+
+        let hash = hash_file(file).await?;
+        self.set_url(demo_id, backend, path, url, hash, key).await
+    }
+
+    /// Move a demo to a different storage location, see `set_url`
+    pub async fn set_storage(
+        &self,
+        demo_id: impl Into<DemoId>,
+        storage: &Storage,
+        hash: [u8; 16],
+        key: &str,
+    ) -> Result<(), Error> {
+        self.set_url(
+            demo_id,
+            &storage.backend,
+            &storage.path,
+            &storage.url,
+            hash,
+            key,
+        )
+        .await
+    }
+
+    /// Set the url of a demo, using the access key of the client as key
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MissingKey` if no access key is configured
+    pub async fn set_url_with_stored_key(
+        &self,
+        demo_id: impl Into<DemoId>,
+        backend: &str,
+        path: &str,
+        url: &str,
+        hash: [u8; 16],
+    ) -> Result<(), Error> {
+        let key = self.stored_key()?;
+        self.set_url(demo_id, backend, path, url, hash, key).await
+    }
+
+    /// Delete a demo, requires the api key of the uploader
+    #[instrument(skip(self, demo_id, key), fields(demo_id))]
+    pub async fn delete_demo(&self, demo_id: impl Into<DemoId>, key: &str) -> Result<(), Error> {
+        let demo_id = demo_id.into();
+        Span::current().record("demo_id", demo_id.0);
+
+        let request = self
+            .request(Method::DELETE, self.url(format!("demos/{}", demo_id))?)
+            .form(&[("key", key)]);
+        let response = self.send(request).await?;
+        self.invalidate_demo(demo_id);
+
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::DemoNotFound(demo_id));
+        }
+
+        error_for_status(response).await?;
+
+        Ok(())
+    }
+
+    /// Delete a demo, using the access key of the client as the api key of the uploader
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MissingKey` if no access key is configured
+    pub async fn delete_demo_with_stored_key(
+        &self,
+        demo_id: impl Into<DemoId>,
+    ) -> Result<(), Error> {
+        let key = self.stored_key()?;
+        self.delete_demo(demo_id, key).await
+    }
+
+    #[instrument(skip(self, body, key))]
+    pub async fn upload_demo(
+        &self,
+        file_name: String,
+        body: Vec<u8>,
+        red: String,
+        blue: String,
+        key: String,
+    ) -> Result<DemoId, Error> {
+        self.upload_maybe_private_demo(file_name, body.into(), red, blue, key, false)
+            .await
+    }
+
+    /// Upload a demo, using the access key of the client as api key
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::MissingKey` if no access key is configured
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let client = ApiClient::new().with_access_key("my_key");
+    ///
+    /// let data = std::fs::read("match.dem")?;
+    /// let id = client
+    ///     .upload_demo_with_stored_key("match.dem".into(), data, "RED".into(), "BLU".into())
+    ///     .await?;
+    /// println!("uploaded as {}", id);
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub async fn upload_demo_with_stored_key(
+        &self,
+        file_name: String,
+        body: Vec<u8>,
+        red: String,
+        blue: String,
+    ) -> Result<DemoId, Error> {
+        let key = self.stored_key()?.to_string();
+        self.upload_demo(file_name, body, red, blue, key).await
+    }
+
+    /// Upload a demo from any source that can be converted into a request body
+    ///
+    /// This allows uploading demos without reading them into memory first, for example by using a
+    /// `tokio::fs::File` or a body created with `reqwest::Body::wrap_stream`.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let file = tokio::fs::File::open("match.dem").await?;
+    /// let id = client
+    ///     .upload_demo_stream(
+    ///         "match.dem".into(),
+    ///         file,
+    ///         "RED".into(),
+    ///         "BLU".into(),
+    ///         "api key".into(),
+    ///     )
+    ///     .await?;
+    /// println!("uploaded as {}", id);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, body, key))]
+    pub async fn upload_demo_stream(
+        &self,
+        file_name: String,
+        body: impl Into<Body>,
+        red: String,
+        blue: String,
+        key: String,
+    ) -> Result<DemoId, Error> {
+        self.upload_maybe_private_demo(file_name, body.into(), red, blue, key, false)
+            .await
+    }
+
+    #[instrument(skip(self, body, key))]
+    pub async fn upload_private_demo(
+        &self,
+        file_name: String,
+        body: Vec<u8>,
+        red: String,
+        blue: String,
+        key: String,
+    ) -> Result<DemoId, Error> {
+        self.upload_maybe_private_demo(file_name, body.into(), red, blue, key, true)
+            .await
+    }
+
+    /// Upload a demo and fetch the stored demo data afterwards
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let data = std::fs::read("match.dem")?;
+    /// let demo = client
+    ///     .upload_demo_full(
+    ///         "match.dem".into(),
+    ///         data,
+    ///         "RED".into(),
+    ///         "BLU".into(),
+    ///         "api key".into(),
+    ///     )
+    ///     .await?;
+    /// println!("uploaded {} as {}", demo.map, demo.id);
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, body, key))]
+    pub async fn upload_demo_full(
+        &self,
+        file_name: String,
+        body: impl Into<Body>,
+        red: String,
+        blue: String,
+        key: String,
+    ) -> Result<Demo, Error> {
+        let id = self
+            .upload_maybe_private_demo(file_name, body.into(), red, blue, key, false)
+            .await?;
+        self.get(id).await
+    }
+
+    /// Upload every `.dem` file in a directory, uploading up to `concurrency` demos at the same time
+    ///
+    /// Subdirectories are not included. Results are yielded as soon as an upload is finished and a failed upload,
+    /// for example of a demo that was already uploaded, doesn't affect the other demos. If the directory can't be
+    /// read, the stream only yields the error for the directory.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// # use demostf_client::ApiClient;
+    /// use futures_util::StreamExt;
+    /// use std::path::Path;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let mut uploads = client.upload_dir(Path::new("demos"), "RED", "BLU", "my_key", 2);
+    ///
+    /// while let Some((path, result)) = uploads.next().await {
+    ///     match result {
+    ///         Ok(id) => println!("{}: uploaded as {}", path.display(), id),
+    ///         Err(e) => println!("{}: {}", path.display(), e),
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn upload_dir<'a>(
+        &'a self,
+        dir: &Path,
+        red: &'a str,
+        blue: &'a str,
+        key: &'a str,
+        concurrency: usize,
+    ) -> impl Stream<Item = (PathBuf, Result<DemoId, Error>)> + 'a {
+        let dir = dir.to_path_buf();
+        stream::once(async move {
+            match demo_files(&dir).await {
+                Ok(files) => stream::iter(files)
+                    .map(move |path| async move {
+                        let result = self.upload_file(&path, red, blue, key).await;
+                        (path, result)
+                    })
+                    .buffer_unordered(concurrency.max(1))
+                    .left_stream(),
+                Err(e) => stream::iter([(dir, Err(Error::Read(e)))]).right_stream(),
+            }
+        })
+        .flatten()
+    }
+
+    #[instrument(skip(self, key))]
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn upload_file(
+        &self,
+        path: &Path,
+        red: &str,
+        blue: &str,
+        key: &str,
+    ) -> Result<DemoId, Error> {
+        let body = tokio::fs::read(path).await.map_err(Error::Read)?;
+        let file_name = path
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned();
+        self.upload_demo(file_name, body, red.into(), blue.into(), key.into())
+            .await
+    }
+
+    async fn upload_maybe_private_demo(
+        &self,
+        file_name: String,
+        body: Body,
+        red: String,
+        blue: String,
+        key: String,
+        private: bool,
+    ) -> Result<DemoId, Error> {
+        let form = multipart::Form::new()
+            .text("red", red)
+            .text("blue", blue)
+            .text("name", file_name.clone())
+            .text("key", key)
+            .text("private", if private { "1" } else { "0" });
+
+        let file = multipart::Part::stream(body)
+            .file_name(file_name)
+            .mime_str("application/octet-stream")?;
+
+        let form = form.part("demo", file);
+
+        let request = self
+            .request(Method::POST, self.url("upload")?)
+            .multipart(form);
+        let response = self.send(request).await?;
+        let resp = error_for_status(response).await?.text().await?;
+
+        if let Some(error) = upload_error(&resp) {
+            return Err(error);
+        }
+
+        parse_upload_response(&resp).ok_or(Error::InvalidResponse(resp))
+    }
+
+    /// Request the demo file, returning the response without reading the demo data
+    ///
+    /// The request uses the same timeout as the other download methods, scaled by the duration of the demo.
+    /// Unlike `Demo::save`, the md5 hash of the data is not verified.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// # use demostf_client::ApiClient;
+    /// #
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), demostf_client::Error> {
+    /// # let client = ApiClient::default();
+    /// #
+    /// let demo = client.get(9).await?;
+    /// let response = client.download_response(&demo).await?;
+    /// println!("{:?} bytes", response.content_length());
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[instrument(skip(self, demo), fields(demo_id = demo.id.0))]
+    pub async fn download_response(&self, demo: &Demo) -> Result<Response, Error> {
+        self.download_demo(&demo.url, demo.duration).await
+    }
+
+    pub(crate) async fn download_demo(&self, url: &str, duration: u16) -> Result<Response, Error> {
+        let response = self.send(self.download_demo_request(url, duration)).await?;
+        error_for_status(response).await
+    }
+
+    /// Request the demo file starting from byte `start`
+    ///
+    /// Servers that don't support range requests will respond with the full file instead,
+    /// check the response status for `206 Partial Content` to see if the range was applied.
+    pub(crate) async fn download_demo_range(
+        &self,
+        url: &str,
+        duration: u16,
+        start: u64,
+    ) -> Result<Response, Error> {
+        trace!(url = url, start = start, "requesting demo file range");
+        let request = self
+            .download_demo_request(url, duration)
+            .header(RANGE, format!("bytes={}-", start));
+        let response = self.send(request).await?;
+        // when the range starts at the end of the demo there is nothing left to download,
+        // the response is passed on so the caller can finish the download
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE
+            && unsatisfied_range_length(&response) == Some(start)
+        {
+            return Ok(response);
+        }
+        error_for_status(response).await
+    }
+
+    fn download_demo_request(&self, url: &str, duration: u16) -> RequestBuilder {
+        let base_timeout = self.options.timeout.unwrap_or(self.base_timeout);
+        let timeout = download_timeout(base_timeout, duration);
+        trace!(url = url, timeout = debug(timeout), "requesting demo file");
+        // demo files are requested uncompressed to keep the content length and range requests intact
+        self.request(Method::GET, url)
+            .header(ACCEPT_ENCODING, "identity")
+            .timeout(timeout)
+    }
+}
+
+/// Timeout for downloading a demo of `duration_secs` seconds
+///
+/// The base timeout covers demos of up to 15 minutes, longer demos get proportionally more time.
+/// With the default base timeout of 15s this allows 1s per minute of demo (~1mb) with a minimum of 15s,
+/// so a 30 minute demo gets a 30s timeout.
+fn download_timeout(base: Duration, duration_secs: u16) -> Duration {
+    let scale = (f32::from(duration_secs) / 900.0).max(1.0);
+    base.mul_f32(scale)
+}
+
+pub(crate) fn validate_page(page: u32) -> Result<(), Error> {
+    if page == 0 {
+        return Err(Error::InvalidPage {
+            requested: page,
+            reason: "pages start at 1",
+        });
+    }
+    if page > MAX_PAGE {
+        return Err(Error::InvalidPage {
+            requested: page,
+            reason: "page is past the maximum number of demos",
+        });
+    }
+    Ok(())
+}
+
+/// Validate the page of a user search, the offset of the page has to fit in the server's 32 bit offset
+fn validate_user_page(page: u32, limit: u32) -> Result<(), Error> {
+    if page == 0 {
+        return Err(Error::InvalidPage {
+            requested: page,
+            reason: "pages start at 1",
+        });
+    }
+    if u64::from(page - 1) * u64::from(limit) > i32::MAX as u64 {
+        return Err(Error::InvalidPage {
+            requested: page,
+            reason: "page is past the maximum number of users for the limit",
+        });
+    }
+    Ok(())
+}
+
+/// Prefix of the response when a demo with the same hash has already been uploaded, followed by the url of the existing demo
+const DUPLICATE_DEMO_PREFIX: &str = "Demo already uploaded to ";
+
+/// Recognize the rejection messages returned by the upload endpoint
+///
+/// Only the exact messages sent by the server are recognized, any other response is left to the caller.
+fn upload_error(response: &str) -> Option<Error> {
+    match response.trim() {
+        "Invalid key" => Some(Error::InvalidApiKey),
+        "Uploader is blacklisted" => Some(Error::Blacklisted),
+        message @ ("Not an HL2 demo"
+        | "Not a TF2 demo"
+        | "Demos need to be at least 5 minutes"
+        | "Error while parsing demo") => Some(Error::InvalidDemo(message.to_string())),
+        response if response.starts_with(DUPLICATE_DEMO_PREFIX) => Some(Error::DuplicateDemo {
+            existing: parse_upload_response(&response[DUPLICATE_DEMO_PREFIX.len()..]),
+        }),
+        _ => None,
+    }
+}
+
+/// Find the id of the uploaded demo in the upload response
+///
+/// The server responds with the url of the uploaded demo, but a json object with an `id` field,
+/// a bare id or a url surrounded by other text are accepted as well.
+fn parse_upload_response(response: &str) -> Option<DemoId> {
+    let response = response.trim();
+    if let Ok(serde_json::Value::Object(object)) = serde_json::from_str(response) {
+        let id = object.get("id")?;
+        return id
+            .as_u64()
+            .or_else(|| id.as_str()?.parse().ok())
+            .and_then(|id| u32::try_from(id).ok())
+            .map(DemoId);
+    }
+
+    response.split_whitespace().rev().find_map(|word| {
+        let path = word.split(['?', '#']).next().unwrap_or_default();
+        let path = path.trim_end_matches(|c: char| !c.is_ascii_alphanumeric());
+        let tail = path.rsplit('/').next().unwrap_or_default();
+        u32::from_str(tail).ok().map(DemoId)
+    })
+}
+
+/// Get the demo id from the last path segment of a demo url
+fn demo_id_from_url(url: &str) -> Option<DemoId> {
+    let path = url.trim().split(['?', '#']).next().unwrap_or_default();
+    let tail = path.trim_end_matches('/').rsplit('/').next()?;
+    u32::from_str(tail).ok().map(DemoId)
+}
+
+#[test]
+fn test_demo_id_from_url() {
+    for url in [
+        "https://demos.tf/447678",
+        "https://demos.tf/447678/",
+        "https://demos.tf/447678?foo=bar",
+        "https://demos.tf/447678#chat",
+        "demos.tf/447678",
+        "https://api.demos.tf/demos/447678",
+        " https://demos.tf/447678\n",
+    ] {
+        assert_eq!(demo_id_from_url(url), Some(DemoId(447678)), "{}", url);
+    }
+    for url in [
+        "https://demos.tf/",
+        "https://demos.tf/profiles/76561198024494988x",
+        "https://demos.tf/abc",
+        "",
+    ] {
+        assert_eq!(demo_id_from_url(url), None, "{}", url);
+    }
+}
+
+/// Calculate the md5 hash of a file
+#[cfg(not(target_arch = "wasm32"))]
+async fn hash_file(path: &Path) -> Result<[u8; 16], Error> {
+    let mut file = File::open(path).await.map_err(Error::Read)?;
+    let mut context = Md5Context::new();
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer).await.map_err(Error::Read)?;
+        if read == 0 {
+            break;
+        }
+        context.consume(&buffer[..read]);
+    }
+    Ok(context.compute())
+}
+
+/// Parse a json response body
+#[cfg(not(feature = "simd"))]
+async fn parse_json<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
+    Ok(response.json().await?)
+}
+
+/// Parse a json response body using simd-json
+#[cfg(feature = "simd")]
+async fn parse_json<T: DeserializeOwned>(response: Response) -> Result<T, Error> {
+    let mut body = response.bytes().await?.to_vec();
+    simd_json::serde::from_slice(&mut body).map_err(|e| Error::InvalidResponse(e.to_string()))
+}
+
+/// Turn an error response into an error, keeping the response body for server errors
+async fn error_for_status(response: Response) -> Result<Response, Error> {
+    let status = response.status();
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return Err(Error::RateLimited {
+            retry_after: retry_after(&response),
+        });
+    }
+    if status.is_server_error() {
+        let body = response.text().await.unwrap_or_default();
+        return Err(Error::ServerError {
+            status: status.as_u16(),
+            body,
+        });
+    }
+    Ok(response.error_for_status()?)
+}
+
+/// Get the total length of the resource from the `Content-Range: bytes */<length>` header of a 416 response
+fn unsatisfied_range_length(response: &Response) -> Option<u64> {
+    let value = response.headers().get(CONTENT_RANGE)?.to_str().ok()?;
+    value.trim().strip_prefix("bytes */")?.parse().ok()
+}
+
+/// Send a request, logging how long it took until the response headers were received
+///
+/// Time can't be measured on wasm, so requests are sent without logging there.
+#[cfg(not(target_arch = "wasm32"))]
+async fn timed(
+    request: impl std::future::Future<Output = Result<Response, reqwest::Error>>,
+) -> Result<Response, Error> {
+    let start = std::time::Instant::now();
+    let result = request.await;
+    let elapsed = start.elapsed();
+    match &result {
+        Ok(response) => debug!(
+            url = display(response.url()),
+            status = response.status().as_u16(),
+            elapsed = debug(elapsed),
+            "request finished"
+        ),
+        Err(e) => debug!(
+            error = display(e),
+            elapsed = debug(elapsed),
+            "request failed"
+        ),
+    }
+    Ok(result?)
+}
+
+#[cfg(target_arch = "wasm32")]
+async fn timed(
+    request: impl std::future::Future<Output = Result<Response, reqwest::Error>>,
+) -> Result<Response, Error> {
+    Ok(request.await?)
+}
+
+/// The `.dem` files in a directory, sorted by path
+#[cfg(not(target_arch = "wasm32"))]
+async fn demo_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut entries = tokio::fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        let is_demo = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("dem"));
+        if is_demo && entry.file_type().await?.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// Parse the `Retry-After` header, either as a number of seconds or as a date
+///
+/// Dates are only supported with the `time` feature.
+fn retry_after(response: &Response) -> Option<Duration> {
+    let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse() {
+        return Some(Duration::from_secs(seconds));
+    }
+    #[cfg(feature = "time")]
+    {
+        let date = OffsetDateTime::parse(value, &Rfc2822).ok()?;
+        Some(
+            (date - OffsetDateTime::now_utc())
+                .try_into()
+                .unwrap_or_default(),
+        )
+    }
+    #[cfg(not(feature = "time"))]
+    None
+}
+
+enum ChatState {
+    Start,
+    Reading {
+        body: ByteStream,
+        parser: JsonArrayParser,
+        pending: VecDeque<ChatMessage>,
+    },
+    Done,
+}
+
+struct PageCursor {
+    params: ListParams,
+    page: u32,
+}
+
+#[test]
+fn test_url() {
+    assert_eq!(
+        "https://example.com/demos",
+        ApiClient::with_base_url("https://example.com")
+            .unwrap()
+            .url("demos")
+            .unwrap()
+            .to_string()
+    );
+    assert_eq!(
+        "https://example.com/demos",
+        ApiClient::with_base_url("https://example.com/")
+            .unwrap()
+            .url("demos")
+            .unwrap()
+            .to_string()
+    );
+    assert_eq!(
+        "https://example.com/sub/demos",
+        ApiClient::with_base_url("https://example.com/sub/")
+            .unwrap()
+            .url("demos")
+            .unwrap()
+            .to_string()
+    );
+    assert_eq!(
+        "https://example.com/sub/demos",
+        ApiClient::with_base_url("https://example.com/sub")
+            .unwrap()
+            .url("demos")
+            .unwrap()
+            .to_string()
+    );
+    assert_eq!(
+        "https://example.com/sub/demos",
+        ApiClient::with_url(
+            Url::parse("https://example.com/sub").unwrap(),
+            Duration::from_secs(15)
+        )
+        .unwrap()
+        .url("demos")
+        .unwrap()
+        .to_string()
+    );
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_sub_path() {
+    let user = serde_json::json!({"id": 1, "steamid": "76561198024494988", "name": "Icewind"});
+    let (url, server) = test_server(vec![
+        json_response(&crate::test_demo_json().to_string()),
+        json_response("[]"),
+        json_response(&user.to_string()),
+        json_response("[]"),
+    ]);
+    let client = ApiClient::with_base_url(url.join("sub").unwrap()).unwrap();
+
+    client.get(1).await.unwrap();
+    client.get_chat(1).await.unwrap();
+    client.get_user(1).await.unwrap();
+    client.search_users("icewind").await.unwrap();
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("GET /sub/demos/1 "));
+    assert!(requests[1].starts_with("GET /sub/demos/1/chat "));
+    assert!(requests[2].starts_with("GET /sub/users/1 "));
+    assert!(requests[3].starts_with("GET /sub/users/search?query=icewind "));
+}
+
+#[test]
+fn test_accessors() {
+    let client = ApiClient::new();
+    assert_eq!(client.base_url().as_str(), ApiClient::DEMOS_TF_BASE_URL);
+    assert!(!client.has_access_key());
+
+    let client = ApiClient::builder()
+        .base_url("https://example.com/sub")
+        .access_key("key")
+        .build()
+        .unwrap();
+    assert_eq!(client.base_url().as_str(), "https://example.com/sub/");
+    assert!(client.has_access_key());
+
+    let mut client = client;
+    client.clear_access_key();
+    assert!(!client.has_access_key());
+    assert!(client.with_access_key("key").has_access_key());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_access_key_header() {
+    let (url, server) = test_server(vec![
+        json_response("[]"),
+        json_response(&crate::test_demo_json().to_string()),
+        json_response("[]"),
+    ]);
+    let client = ApiClient::with_base_url(url)
+        .unwrap()
+        .with_access_key("private-key");
+
+    client.list(ListParams::default(), 1).await.unwrap();
+    client.get(1).await.unwrap();
+    client.get_chat(1).await.unwrap();
+
+    for request in server.join().unwrap() {
+        let request = request.to_ascii_lowercase();
+        assert!(
+            request.contains("\r\naccess-key: private-key\r\n"),
+            "{}",
+            request
+        );
+        assert!(!request.contains("access_key"), "{}", request);
+    }
+}
+
+#[test]
+fn test_builder() {
+    let client = ApiClient::builder()
+        .base_url("https://example.com/sub")
+        .reqwest_client(Client::new())
+        .timeout(Duration::from_secs(30))
+        .build()
+        .unwrap();
+    assert_eq!(
+        "https://example.com/sub/demos",
+        client.url("demos").unwrap().to_string()
+    );
+    assert_eq!(Duration::from_secs(30), client.base_timeout);
+
+    assert!(matches!(
+        ApiClient::builder().base_url("not a url").build(),
+        Err(Error::InvalidBaseUrl)
+    ));
+}
+
+/// Serve canned http responses, one per connection, returning the raw requests that were received
+#[cfg(test)]
+fn test_server(responses: Vec<String>) -> (Url, std::thread::JoinHandle<Vec<String>>) {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    fn is_complete(request: &[u8]) -> bool {
+        let request = String::from_utf8_lossy(request);
+        let Some((head, body)) = request.split_once("\r\n\r\n") else {
+            return false;
+        };
+        if head
+            .to_ascii_lowercase()
+            .contains("transfer-encoding: chunked")
+        {
+            return body.ends_with("0\r\n\r\n");
+        }
+        let content_length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .and_then(|(_, value)| value.trim().parse().ok())
+            .unwrap_or(0);
+        body.len() >= content_length
+    }
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    let handle = std::thread::spawn(move || {
+        let mut requests = Vec::new();
+        for response in responses {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+            while !is_complete(&request) {
+                let read = stream.read(&mut buffer).unwrap();
+                if read == 0 {
+                    break;
+                }
+                request.extend_from_slice(&buffer[..read]);
+            }
+            stream.write_all(response.as_bytes()).unwrap();
+            requests.push(String::from_utf8_lossy(&request).into_owned());
+        }
+        requests
+    });
+    (url, handle)
+}
+
+#[cfg(test)]
+fn http_response(status: &str, body: &str) -> String {
+    format!(
+        "HTTP/1.1 {}\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    )
+}
+
+#[cfg(test)]
+fn json_response(body: &str) -> String {
+    http_response("200 OK", body)
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_user_agent() {
+    let (url, server) = test_server(vec![json_response("[]")]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    client.search_users("foo").await.unwrap();
+    let request = &server.join().unwrap()[0];
+    assert!(request.contains(&format!(
+        "user-agent: {}\r\n",
+        ApiClient::DEFAULT_USER_AGENT
+    )));
+
+    let (url, server) = test_server(vec![json_response("[]")]);
+    let client = ApiClient::builder()
+        .base_url(url)
+        .user_agent("custom-agent")
+        .build()
+        .unwrap();
+    client.search_users("foo").await.unwrap();
+    let request = &server.join().unwrap()[0];
+    assert!(request.contains("user-agent: custom-agent\r\n"));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_invalid_page() {
+    let client = ApiClient::with_base_url("http://localhost").unwrap();
+
+    assert!(matches!(
+        client.list(ListParams::default(), 0).await.unwrap_err(),
+        Error::InvalidPage { requested: 0, .. }
+    ));
+    assert!(matches!(
+        client
+            .list(ListParams::default(), u32::MAX)
+            .await
+            .unwrap_err(),
+        Error::InvalidPage {
+            requested: u32::MAX,
+            ..
+        }
+    ));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_page_size() {
+    let page = |len: usize| {
+        json_response(&serde_json::json!(vec![crate::test_demo_json(); len]).to_string())
+    };
+    let (url, server) = test_server(vec![page(0), page(3), page(50), page(60), page(12)]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    let cloned = client.clone();
+
+    let has_more = |page: u32| {
+        let client = &client;
+        async move {
+            client
+                .list_page(ListParams::default(), page)
+                .await
+                .unwrap()
+                .has_more
+        }
+    };
+
+    assert_eq!(client.page_size(), None);
+    assert!(!has_more(1).await);
+    assert_eq!(client.page_size(), None);
+
+    // short pages don't tell us anything about the page size
+    assert!(!has_more(1).await);
+    assert_eq!(client.page_size(), None);
+
+    assert!(has_more(1).await);
+    assert_eq!(client.page_size(), Some(50));
+    assert_eq!(cloned.page_size(), Some(50));
+
+    // the largest full page is kept
+    assert!(has_more(2).await);
+    assert_eq!(client.page_size(), Some(60));
+    assert!(!has_more(3).await);
+    assert_eq!(client.page_size(), Some(60));
+    server.join().unwrap();
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_list_uploader() {
+    let (url, server) = test_server(vec![json_response("[]")]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let params = ListParams::default()
+        .with_uploader(5)
+        .with_map("cp_process_final");
+    client.list(params, 1).await.unwrap();
+
+    let request = &server.join().unwrap()[0];
+    let request_line = request.lines().next().unwrap();
+    assert!(request_line.contains("uploader=5"));
+    assert!(request_line.contains("map=cp_process_final"));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_search_users_paged() {
+    let users = serde_json::json!([
+        {"id": 1, "steamid": "76561198024494988", "name": "john"},
+        {"id": 2, "steamid": "76561198010628997", "name": "johnny"},
+        {"id": 3, "steamid": "76561197963701107", "name": "big john"},
+    ]);
+    let (url, server) = test_server(vec![
+        json_response(&users.to_string()),
+        json_response(&users.to_string()),
+    ]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let ids = |users: Vec<User>| users.into_iter().map(|user| user.id).collect::<Vec<_>>();
     assert_eq!(
-        "https://example.com/demos",
-        ApiClient::with_base_url("https://example.com")
-            .unwrap()
-            .url("demos")
-            .unwrap()
-            .to_string()
+        ids(client.search_users_paged("john", 1, 2).await.unwrap()),
+        [UserId(1), UserId(2)]
     );
     assert_eq!(
-        "https://example.com/demos",
-        ApiClient::with_base_url("https://example.com/")
-            .unwrap()
-            .url("demos")
-            .unwrap()
-            .to_string()
+        ids(client.search_users_paged("john", 2, 2).await.unwrap()),
+        [UserId(3)]
     );
+    assert!(matches!(
+        client.search_users_paged("john", 0, 2).await,
+        Err(Error::InvalidPage { requested: 0, .. })
+    ));
+    assert!(matches!(
+        client.search_users_paged("john", u32::MAX, 2).await,
+        Err(Error::InvalidPage {
+            requested: u32::MAX,
+            reason: "page is past the maximum number of users for the limit"
+        })
+    ));
+
+    let requests = server.join().unwrap();
+    assert!(requests[1].starts_with("GET /users/search?query=john&page=2&limit=2 "));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_resolve_uploaders() {
+    let user = serde_json::json!({"id": 2, "steamid": "76561198010628997", "name": "freak"});
+    let (url, server) = test_server(vec![json_response(&user.to_string())]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let demo = crate::test_demo();
+    let by_id = |id| Demo {
+        id: DemoId(id),
+        uploader: crate::UserRef::Id(UserId(2)),
+        ..demo.clone()
+    };
+    let demos = stream::iter(vec![Ok(demo.clone()), Ok(by_id(2)), Ok(by_id(3))]);
+
+    let resolved: Vec<_> = client.resolve_uploaders(demos).try_collect().await.unwrap();
+    let uploaders: Vec<_> = resolved
+        .iter()
+        .map(|(demo, user)| (demo.id, user.name.as_str()))
+        .collect();
     assert_eq!(
-        "https://example.com/sub/demos",
-        ApiClient::with_base_url("https://example.com/sub/")
-            .unwrap()
-            .url("demos")
-            .unwrap()
-            .to_string()
+        uploaders,
+        [
+            (DemoId(1), "Icewind"),
+            (DemoId(2), "freak"),
+            (DemoId(3), "freak")
+        ]
+    );
+    // the uploader of the last two demos is only requested once
+    assert_eq!(server.join().unwrap().len(), 1);
+
+    let demos = stream::iter(vec![
+        Ok(demo.clone()),
+        Err(Error::TimeOut),
+        Ok(demo.clone()),
+    ]);
+    let results: Vec<_> = client.resolve_uploaders(demos).collect().await;
+    assert_eq!(results.len(), 2);
+    assert!(matches!(results[1], Err(Error::TimeOut)));
+
+    // demos with a known uploader are still resolved when requesting the other uploaders fails
+    let (url, server) = test_server(vec![http_response("500 Internal Server Error", "")]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    let known = Demo {
+        id: DemoId(4),
+        ..demo.clone()
+    };
+    let demos = stream::iter(vec![
+        Ok(demo.clone()),
+        Ok(by_id(2)),
+        Ok(by_id(3)),
+        Ok(known),
+    ]);
+    let results: Vec<_> = client.resolve_uploaders(demos).collect().await;
+    assert_eq!(results.len(), 3);
+    assert!(matches!(&results[0], Ok((demo, _)) if demo.id == DemoId(1)));
+    assert!(matches!(
+        results[1],
+        Err(Error::ServerError { status: 500, .. })
+    ));
+    assert!(matches!(&results[2], Ok((demo, _)) if demo.id == DemoId(4)));
+    server.join().unwrap();
+}
+
+#[cfg(all(test, feature = "cache"))]
+#[tokio::test]
+async fn test_cache() {
+    let user = serde_json::json!({"id": 1, "steamid": "76561198024494988", "name": "Icewind"});
+    let demo = crate::test_demo_json().to_string();
+    let (url, server) = test_server(vec![
+        json_response(&demo),
+        json_response(&user.to_string()),
+        http_response("200 OK", ""),
+        json_response(&demo),
+    ]);
+    let client = ApiClient::with_base_url(url)
+        .unwrap()
+        .with_cache(CacheConfig {
+            capacity: 10,
+            ttl: Duration::from_secs(60),
+        });
+    let cloned = client.clone();
+
+    client.get(1).await.unwrap();
+    cloned.get(1).await.unwrap();
+    client.get_user(1).await.unwrap();
+    cloned.get_user(1).await.unwrap();
+
+    // deleting the demo removes it from the cache
+    client.delete_demo(1, "key").await.unwrap();
+    cloned.get(1).await.unwrap();
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests.len(), 4);
+    assert!(requests[3].starts_with("GET /demos/1 "));
+}
+
+#[cfg(all(test, feature = "cache"))]
+#[tokio::test]
+async fn test_cache_access_key() {
+    let demo = crate::test_demo_json().to_string();
+    let (url, server) = test_server(vec![json_response(&demo), json_response(&demo)]);
+    let client = ApiClient::with_base_url(url)
+        .unwrap()
+        .with_cache(CacheConfig::default())
+        .with_access_key("private-key");
+    let mut without_key = client.clone();
+    without_key.clear_access_key();
+
+    client.get(1).await.unwrap();
+    without_key.get(1).await.unwrap();
+    // demos requested without a key are cached for both clients
+    without_key.get(1).await.unwrap();
+    client.get(1).await.unwrap();
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests.len(), 2);
+    assert!(requests[0]
+        .to_ascii_lowercase()
+        .contains("access-key: private-key"));
+    assert!(!requests[1].to_ascii_lowercase().contains("access-key"));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_list_since() {
+    let mut demo = crate::test_demo_json();
+    demo["id"] = 6.into();
+    let mut next = crate::test_demo_json();
+    next["id"] = 7.into();
+    let (url, server) = test_server(vec![json_response(
+        &serde_json::json!([demo, next]).to_string(),
+    )]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let params = ListParams::default()
+        .with_after_id(1)
+        .with_order(ListOrder::Descending);
+    let demos: Vec<Demo> = client.list_since(5, params).try_collect().await.unwrap();
+    let ids: Vec<_> = demos.iter().map(|demo| demo.id).collect();
+    assert_eq!(ids, [DemoId(6), DemoId(7)]);
+
+    let requests = server.join().unwrap();
+    let request_line = requests[0].lines().next().unwrap();
+    assert!(request_line.contains("after_id=5"), "{}", request_line);
+    assert!(request_line.contains("order=ASC"), "{}", request_line);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_set_url_from_file() {
+    let (url, server) = test_server(vec![http_response("200 OK", "")]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let path = std::env::temp_dir().join(format!("demostf-set-url-{}.dem", std::process::id()));
+    std::fs::write(&path, b"demo data").unwrap();
+    client
+        .set_url_from_file(
+            1,
+            "static",
+            "/1.dem",
+            "https://example.com/1.dem",
+            &path,
+            "key",
+        )
+        .await
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert!(matches!(
+        client
+            .set_url_from_file(
+                1,
+                "static",
+                "/1.dem",
+                "https://example.com/1.dem",
+                &path,
+                "key"
+            )
+            .await,
+        Err(Error::Read(_))
+    ));
+
+    let request = &server.join().unwrap()[0];
+    let expected = hex::encode(md5::compute(b"demo data").0);
+    assert!(request.contains(&format!("hash={}", expected)));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_set_storage() {
+    let (url, server) = test_server(vec![http_response("200 OK", "")]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let demo = crate::test_demo();
+    let storage = Storage {
+        backend: "freezer".into(),
+        ..demo.storage()
+    };
+    assert_eq!(demo.storage().backend, "static");
+    client
+        .set_storage(demo.id, &storage, demo.hash, "key")
+        .await
+        .unwrap();
+
+    let request = &server.join().unwrap()[0];
+    assert!(request.starts_with("POST /demos/1/url "));
+    assert!(request.contains("backend=freezer"));
+    assert!(request.contains("hash=01b2265d875026b91d59a2785abfd50d"));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_resolve_many() {
+    use crate::UserRef;
+
+    let user = |id: u32, name: &str| serde_json::json!({"id": id, "steamid": "76561198024494988", "name": name});
+    let (url, server) = test_server(vec![json_response(&user(2, "second").to_string())]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let refs = [
+        UserRef::Id(UserId(2)),
+        serde_json::from_value(user(1, "first")).unwrap(),
+        UserRef::Id(UserId(2)),
+    ];
+    let users = UserRef::resolve_many(&refs, &client).await.unwrap();
+    let names: Vec<_> = users.iter().map(|user| user.name.as_str()).collect();
+    assert_eq!(names, ["second", "first", "second"]);
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].starts_with("GET /users/2 "));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_request_timeout() {
+    use std::net::TcpListener;
+
+    // accept connections but never respond
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+
+    let client = ApiClient::with_base_url(url).unwrap();
+    let err = client
+        .with_options(RequestOptions::default().with_timeout(Duration::from_millis(50)))
+        .search_users("foo")
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::TimeOut));
+    drop(listener);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_chat_stream() {
+    let chat = serde_json::json!([
+        {"user": "a", "time": 0, "message": "first"},
+        {"user": "b", "time": 10, "message": "second"},
+    ]);
+    let (url, server) = test_server(vec![
+        json_response(&chat.to_string()),
+        http_response("404 Not Found", ""),
+    ]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let messages: Vec<_> = client.chat_stream(1).try_collect().await.unwrap();
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[1].user, "b");
+    assert_eq!(messages[1].message, "second");
+
+    let results: Vec<_> = client.chat_stream(2).collect().await;
+    assert_eq!(results.len(), 1);
+    assert!(matches!(results[0], Err(Error::DemoNotFound(DemoId(2)))));
+    server.join().unwrap();
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_get_chat_between() {
+    let chat = serde_json::json!([
+        {"user": "a", "time": 0, "message": "first"},
+        {"user": "b", "time": 10, "message": "second"},
+        {"user": "c", "time": 20, "message": "third"},
+        {"user": "d", "time": 30, "message": "fourth"},
+    ]);
+    let (url, server) = test_server(vec![json_response(&chat.to_string())]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let messages = client.get_chat_between(1, 10, 20).await.unwrap();
+    let messages: Vec<_> = messages.iter().map(|m| m.message.as_str()).collect();
+    assert_eq!(messages, ["second", "third"]);
+
+    assert!(client.get_chat_between(1, 20, 10).await.unwrap().is_empty());
+    assert_eq!(server.join().unwrap().len(), 1);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_rate_limited() {
+    let rate_limited = |retry_after: &str| {
+        format!(
+            "HTTP/1.1 429 Too Many Requests\r\nretry-after: {retry_after}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n"
+        )
+    };
+
+    let (url, server) = test_server(vec![rate_limited("120")]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    assert!(matches!(
+        client.search_users("foo").await.unwrap_err(),
+        Error::RateLimited {
+            retry_after: Some(retry_after)
+        } if retry_after == Duration::from_secs(120)
+    ));
+    server.join().unwrap();
+
+    let (url, server) = test_server(vec![rate_limited("0"), json_response("[]")]);
+    let client = ApiClient::builder()
+        .base_url(url)
+        .retry(RetryConfig::default())
+        .build()
+        .unwrap();
+    client.search_users("foo").await.unwrap();
+    assert_eq!(server.join().unwrap().len(), 2);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_ping() {
+    use std::net::TcpListener;
+
+    let (url, server) = test_server(vec![
+        http_response("200 OK", ""),
+        http_response("503 Service Unavailable", "maintenance"),
+    ]);
+    let client = ApiClient::with_base_url(url.join("sub/").unwrap()).unwrap();
+    client.ping().await.unwrap();
+    assert!(matches!(
+        client.ping().await,
+        Err(Error::ServerError { status: 503, .. })
+    ));
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("GET /sub/ "));
+
+    // accept connections but never respond
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+    let client = ApiClient::with_base_url(url.clone()).unwrap();
+    let err = client
+        .with_options(RequestOptions::default().with_timeout(Duration::from_millis(50)))
+        .ping()
+        .await
+        .unwrap_err();
+    assert!(matches!(err, Error::TimeOut));
+
+    // refuse connections
+    drop(listener);
+    let err = client.ping().await.unwrap_err();
+    assert!(matches!(err, Error::Request(_)));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_exists() {
+    let (url, server) = test_server(vec![
+        json_response(""),
+        http_response("404 Not Found", ""),
+        http_response("405 Method Not Allowed", ""),
+        json_response("{}"),
+    ]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    assert!(client.exists(1).await.unwrap());
+    assert!(!client.exists(2).await.unwrap());
+    assert!(client.exists(3).await.unwrap());
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("HEAD /demos/1 "));
+    assert!(requests[1].starts_with("HEAD /demos/2 "));
+    assert!(requests[2].starts_with("HEAD /demos/3 "));
+    assert!(requests[3].starts_with("GET /demos/3 "));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_save_hash_verification() {
+    let (url, server) = test_server(vec![
+        json_response("demo data"),
+        json_response("demo data"),
+        json_response("demo data"),
+        json_response("demo data"),
+        json_response("demo data"),
+        json_response("demo data"),
+    ]);
+    let client = ApiClient::with_base_url(url.clone()).unwrap();
+    let demo = Demo {
+        url: url.join("demo.dem").unwrap().to_string(),
+        hash: [0; 16],
+        ..crate::test_demo()
+    };
+
+    let mut data = Vec::new();
+    demo.save(&client, &mut data).await.unwrap();
+    assert_eq!(data, b"demo data");
+
+    let demo = Demo {
+        hash: md5::compute(b"demo data").0,
+        ..demo
+    };
+    assert_eq!(demo.download_bytes(&client).await.unwrap(), b"demo data");
+    let mut data = Vec::new();
+    demo.save_async(&client, &mut data).await.unwrap();
+    assert_eq!(data, b"demo data");
+
+    let demo = Demo {
+        hash: [1; 16],
+        ..demo
+    };
+    assert!(matches!(
+        demo.save(&client, std::io::sink()).await.unwrap_err(),
+        Error::HashMisMatch {
+            expected,
+            calculated: Some(_)
+        } if expected == [1; 16]
+    ));
+    demo.save_unverified(&client, std::io::sink())
+        .await
+        .unwrap();
+    assert!(matches!(
+        demo.save_async(&client, tokio::io::sink())
+            .await
+            .unwrap_err(),
+        Error::HashMisMatch { .. }
+    ));
+    server.join().unwrap();
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_resume_complete_download() {
+    let not_satisfiable = |length: u64| {
+        format!("HTTP/1.1 416 Range Not Satisfiable\r\ncontent-range: bytes */{length}\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+    };
+    let (url, server) = test_server(vec![
+        not_satisfiable(9),
+        not_satisfiable(9),
+        not_satisfiable(12),
+    ]);
+    let client = ApiClient::with_base_url(url.clone()).unwrap();
+    let demo = Demo {
+        url: url.join("demo.dem").unwrap().to_string(),
+        hash: md5::compute(b"demo data").0,
+        ..crate::test_demo()
+    };
+    let existing = |data: &[u8]| {
+        let mut context = Md5Context::new();
+        context.consume(data);
+        crate::ResumeVerification::Verify(context)
+    };
+
+    let mut data = Vec::new();
+    demo.resume_download(&client, &mut data, 9, existing(b"demo data"))
+        .await
+        .unwrap();
+    assert!(data.is_empty());
+
+    assert!(matches!(
+        demo.resume_download(&client, std::io::sink(), 9, existing(b"demo date"))
+            .await
+            .unwrap_err(),
+        Error::HashMisMatch { .. }
+    ));
+
+    assert!(demo
+        .resume_download(&client, std::io::sink(), 9, existing(b"demo data"))
+        .await
+        .is_err());
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].to_ascii_lowercase().contains("range: bytes=9-"));
+}
+
+#[cfg(all(test, feature = "compression"))]
+#[tokio::test]
+async fn test_compression() {
+    let (url, server) = test_server(vec![json_response("[]"), json_response("demo data")]);
+    let client = ApiClient::with_base_url(url.clone()).unwrap();
+
+    client.list(ListParams::default(), 1).await.unwrap();
+    let demo = Demo {
+        url: url.join("demo.dem").unwrap().to_string(),
+        hash: [0; 16],
+        ..crate::test_demo()
+    };
+    demo.save(&client, std::io::sink()).await.unwrap();
+
+    let requests = server.join().unwrap();
+    let accept_encoding = |request: &str| {
+        request.lines().find_map(|line| {
+            line.to_ascii_lowercase()
+                .strip_prefix("accept-encoding: ")
+                .map(String::from)
+        })
+    };
+    let api_encoding = accept_encoding(&requests[0]).unwrap();
+    assert!(api_encoding.contains("gzip"));
+    assert!(api_encoding.contains("br"));
+    assert_eq!(accept_encoding(&requests[1]).as_deref(), Some("identity"));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_download_response() {
+    let (url, server) = test_server(vec![
+        json_response("demo data"),
+        http_response("404 Not Found", ""),
+    ]);
+    let client = ApiClient::with_base_url(url.clone()).unwrap();
+    let demo = Demo {
+        url: url.join("demo.dem").unwrap().to_string(),
+        ..crate::test_demo()
+    };
+
+    let response = client.download_response(&demo).await.unwrap();
+    assert_eq!(response.content_length(), Some(9));
+    assert_eq!(response.text().await.unwrap(), "demo data");
+    assert!(client.download_response(&demo).await.is_err());
+    server.join().unwrap();
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_list_uploads_unknown_user() {
+    let (url, server) = test_server(vec![http_response("404 Not Found", "")]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    let demos = client
+        .list_uploads(SteamID::from(76561197963701107), ListParams::default(), 1)
+        .await
+        .unwrap();
+    assert!(demos.is_empty());
+    server.join().unwrap();
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_upload_count() {
+    let page = |len: usize| json_response(&serde_json::json!(vec![0; len]).to_string());
+
+    let (url, server) = test_server(vec![page(50), page(50), page(0), page(7)]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    let count = client
+        .upload_count(SteamID::from(76561198024494988))
+        .await
+        .unwrap();
+    assert_eq!(count, 107);
+
+    let requests = server.join().unwrap();
+    let pages: Vec<_> = requests
+        .iter()
+        .map(|request| {
+            request
+                .split(['?', '&', ' '])
+                .find(|part| part.starts_with("page="))
+                .unwrap()
+        })
+        .collect();
+    assert_eq!(pages, ["page=1", "page=2", "page=4", "page=3"]);
+
+    let (url, server) = test_server(vec![page(50), page(50), page(0), page(0)]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    let count = client
+        .upload_count(SteamID::from(76561198024494988))
+        .await
+        .unwrap();
+    assert_eq!(count, 100);
+    server.join().unwrap();
+
+    // without a known page size, a short first page is the only page
+    let (url, server) = test_server(vec![page(12)]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    let count = client
+        .upload_count(SteamID::from(76561198024494988))
+        .await
+        .unwrap();
+    assert_eq!(count, 12);
+    server.join().unwrap();
+
+    // a short page seen before counting is never used as page size for the following full pages
+    let (url, server) = test_server(vec![page(3), page(50), page(50), page(20)]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    client.list_raw(ListParams::default(), 1).await.unwrap();
+    let count = client
+        .upload_count(SteamID::from(76561198024494988))
+        .await
+        .unwrap();
+    assert_eq!(count, 170);
+    assert_eq!(client.page_size(), Some(50));
+    let pages: Vec<_> = server.join().unwrap()[1..]
+        .iter()
+        .map(|request| {
+            request
+                .split(['?', '&', ' '])
+                .find(|part| part.starts_with("page="))
+                .unwrap()
+                .to_string()
+        })
+        .collect();
+    assert_eq!(pages, ["page=1", "page=2", "page=4"]);
+
+    // once the page size is known, a short first page is the only page
+    let (url, server) = test_server(vec![page(12)]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    client.page_size.store(50, Ordering::Relaxed);
+    let count = client
+        .upload_count(SteamID::from(76561198024494988))
+        .await
+        .unwrap();
+    assert_eq!(count, 12);
+    server.join().unwrap();
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_download_all() {
+    let (url, server) = test_server(vec![json_response("demo data")]);
+    let client = ApiClient::with_base_url(url.clone()).unwrap();
+    let demo = Demo {
+        name: "red/blue.dem".into(),
+        url: url.join("demo.dem").unwrap().to_string(),
+        hash: [0; 16],
+        ..crate::test_demo()
+    };
+    let missing = Demo {
+        id: DemoId(2),
+        url: "http://127.0.0.1:1/demo.dem".into(),
+        ..demo.clone()
+    };
+
+    let dir = std::env::temp_dir().join(format!("demostf-download-all-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    let mut results: Vec<_> = client
+        .download_all(&[demo, missing], &dir, 2)
+        .collect()
+        .await;
+    results.sort_by_key(|(id, _)| *id);
+
+    let path = results[0].1.as_ref().unwrap();
+    assert_eq!(path, &dir.join("1_red_blue.dem"));
+    assert_eq!(std::fs::read(path).unwrap(), b"demo data");
+    assert!(results[1].1.is_err());
+    assert!(!dir.join("2_red_blue.dem").exists());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    server.join().unwrap();
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_upload_dir() {
+    let (url, server) = test_server(vec![
+        json_response("https://demos.tf/12"),
+        json_response("Demo already uploaded to https://demos.tf/7"),
+    ]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let dir = std::env::temp_dir().join(format!("demostf-upload-dir-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    std::fs::write(dir.join("a.dem"), b"first demo").unwrap();
+    std::fs::write(dir.join("b.DEM"), b"second demo").unwrap();
+    std::fs::write(dir.join("notes.txt"), b"not a demo").unwrap();
+
+    let results: Vec<_> = client
+        .upload_dir(&dir, "red", "blue", "key", 1)
+        .collect()
+        .await;
+    assert_eq!(2, results.len());
+    assert_eq!(results[0].0, dir.join("a.dem"));
+    assert_eq!(results[0].1, Ok(DemoId(12)));
+    assert_eq!(results[1].0, dir.join("b.DEM"));
+    assert_eq!(
+        results[1].1,
+        Err(Error::DuplicateDemo {
+            existing: Some(DemoId(7))
+        })
     );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+    let requests = server.join().unwrap();
+    assert!(requests[0].contains("filename=\"a.dem\""));
+    assert!(requests[0].contains("first demo"));
+
+    let missing = client
+        .upload_dir(&dir, "red", "blue", "key", 1)
+        .collect::<Vec<_>>()
+        .await;
+    assert_eq!(1, missing.len());
+    assert_eq!(missing[0].0, dir);
+    assert!(matches!(missing[0].1, Err(Error::Read(_))));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_upload_demo_stream() {
+    let (url, server) = test_server(vec![json_response("https://demos.tf/12")]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let chunks: Vec<Result<&'static [u8], std::io::Error>> =
+        vec![Ok(b"streamed ".as_slice()), Ok(b"demo data".as_slice())];
+    let id = client
+        .upload_demo_stream(
+            "test.dem".into(),
+            Body::wrap_stream(stream::iter(chunks)),
+            "red".into(),
+            "blue".into(),
+            "key".into(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(id, DemoId(12));
+
+    let request = &server.join().unwrap()[0];
+    assert!(request.starts_with("POST /upload "));
+    assert!(request.contains("streamed "));
+    assert!(request.contains("demo data"));
+}
+
+#[test]
+fn test_download_timeout() {
+    let base = Duration::from_secs(15);
+    assert_eq!(download_timeout(base, 0), Duration::from_secs(15));
+    assert_eq!(download_timeout(base, 60), Duration::from_secs(15));
+    assert_eq!(download_timeout(base, 900), Duration::from_secs(15));
+    assert_eq!(download_timeout(base, 1800), Duration::from_secs(30));
+    assert_eq!(download_timeout(base, 3600), Duration::from_secs(60));
     assert_eq!(
-        "https://example.com/sub/demos",
-        ApiClient::with_base_url("https://example.com/sub")
-            .unwrap()
-            .url("demos")
-            .unwrap()
-            .to_string()
+        download_timeout(Duration::from_secs(30), 1800),
+        Duration::from_secs(60)
+    );
+}
+
+#[test]
+fn test_parse_upload_response() {
+    for response in [
+        "https://demos.tf/12",
+        "https://demos.tf/12/\n",
+        "https://demos.tf/12?private=1",
+        "12",
+        "Demo uploaded to https://demos.tf/12.",
+        r#"{"id": 12, "url": "https://demos.tf/12"}"#,
+        r#"{"id": "12"}"#,
+    ] {
+        assert_eq!(
+            parse_upload_response(response),
+            Some(DemoId(12)),
+            "{}",
+            response
+        );
+    }
+
+    for response in ["", "Upload failed", "https://demos.tf/", r#"{"url": "12"}"#] {
+        assert_eq!(parse_upload_response(response), None, "{}", response);
+    }
+}
+
+#[test]
+fn test_upload_error_invalid_key() {
+    assert!(matches!(
+        upload_error("Invalid key"),
+        Some(Error::InvalidApiKey)
+    ));
+    assert!(upload_error("invalid key for user").is_none());
+}
+
+#[test]
+fn test_upload_error_blacklisted() {
+    assert!(matches!(
+        upload_error("Uploader is blacklisted"),
+        Some(Error::Blacklisted)
+    ));
+    assert!(upload_error("blacklisted").is_none());
+}
+
+#[test]
+fn test_upload_error_duplicate() {
+    assert!(matches!(
+        upload_error("Demo already uploaded to https://demos.tf/12"),
+        Some(Error::DuplicateDemo {
+            existing: Some(DemoId(12))
+        })
+    ));
+    assert!(upload_error("Duplicate demo").is_none());
+}
+
+#[test]
+fn test_upload_error_invalid_demo() {
+    for message in [
+        "Not an HL2 demo",
+        "Not a TF2 demo",
+        "Demos need to be at least 5 minutes",
+        "Error while parsing demo",
+    ] {
+        assert!(
+            matches!(
+                upload_error(message),
+                Some(Error::InvalidDemo(error)) if error == message
+            ),
+            "{}",
+            message
+        );
+    }
+}
+
+#[test]
+fn test_upload_error_unknown() {
+    for response in [
+        "STV available at: https://demos.tf/12",
+        "Something unexpected",
+        "Only tf2 demos are supported",
+        "failed to parse demo",
+        "",
+    ] {
+        assert!(upload_error(response).is_none(), "{}", response);
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_upload_file_name() {
+    let (url, server) = test_server(vec![json_response("https://demos.tf/12")]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    client
+        .upload_demo(
+            "match.dem".into(),
+            b"demo data".to_vec(),
+            "red".into(),
+            "blue".into(),
+            "key".into(),
+        )
+        .await
+        .unwrap();
+
+    let request = &server.join().unwrap()[0];
+    assert!(request.contains(
+        "Content-Disposition: form-data; name=\"demo\"; filename=\"match.dem\"\r\nContent-Type: application/octet-stream"
+    ), "{}", request);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_upload_demo_full() {
+    let (url, server) = test_server(vec![
+        json_response("Demo uploaded to https://demos.tf/1"),
+        json_response(&crate::test_demo_json().to_string()),
+    ]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let demo = client
+        .upload_demo_full(
+            "test.dem".into(),
+            b"demo data".to_vec(),
+            "red".into(),
+            "blue".into(),
+            "key".into(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(demo.id, DemoId(1));
+
+    let requests = server.join().unwrap();
+    assert!(requests[1].starts_with("GET /demos/1 "));
+}
+
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct LogCapture(Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl std::io::Write for LogCapture {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_get_from_url() {
+    let (url, server) = test_server(vec![json_response(&crate::test_demo_json().to_string())]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    let demo = client.get_from_url("https://demos.tf/1/").await.unwrap();
+    assert_eq!(demo.id, DemoId(1));
+    assert_eq!(
+        client.get_from_url("https://demos.tf/").await.unwrap_err(),
+        Error::InvalidDemoUrl("https://demos.tf/".into())
+    );
+
+    let requests = server.join().unwrap();
+    assert!(requests[0].starts_with("GET /demos/1 "));
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_request_id() {
+    let logs = LogCapture::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let (url, server) = test_server(vec![
+        json_response(&crate::test_demo_json().to_string()),
+        json_response(&crate::test_demo_json().to_string()),
+        json_response(&crate::test_demo_json().to_string()),
+    ]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    client.get(1).await.unwrap();
+
+    let counter = Arc::new(AtomicU32::new(0));
+    let client = client.with_request_id_generator(move || {
+        format!("req-{}", counter.fetch_add(1, Ordering::Relaxed) + 1)
+    });
+    client.get(1).await.unwrap();
+    client.get(1).await.unwrap();
+
+    let requests: Vec<_> = server
+        .join()
+        .unwrap()
+        .iter()
+        .map(|request| request.to_ascii_lowercase())
+        .collect();
+    assert!(!requests[0].contains("x-request-id"));
+    assert!(requests[1].contains("x-request-id: req-1\r\n"));
+    assert!(requests[2].contains("x-request-id: req-2\r\n"));
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(logs.contains("request{request_id=\"req-1\"}"), "{}", logs);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_request_duration_logged() {
+    let logs = LogCapture::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let (url, server) = test_server(vec![json_response(&crate::test_demo_json().to_string())]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    client.get(1).await.unwrap();
+    server.join().unwrap();
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    let line = logs
+        .lines()
+        .find(|line| line.contains("request finished"))
+        .unwrap();
+    assert!(line.contains("get{demo_id=1}"), "{}", line);
+    assert!(line.contains("status=200"), "{}", line);
+    assert!(line.contains("elapsed="), "{}", line);
+    assert!(line.contains("/demos/1"), "{}", line);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_keys_not_logged() {
+    use tracing_subscriber::fmt::format::FmtSpan;
+
+    let logs = LogCapture::default();
+    let writer = logs.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::TRACE)
+        .with_span_events(FmtSpan::NEW)
+        .with_ansi(false)
+        .with_writer(move || writer.clone())
+        .finish();
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let (url, server) = test_server(vec![
+        http_response("200 OK", ""),
+        json_response("https://demos.tf/5"),
+        http_response("200 OK", ""),
+    ]);
+    let client = ApiClient::builder()
+        .base_url(url)
+        .access_key("secret-access-key")
+        .build()
+        .unwrap();
+    assert!(!format!("{:?}", client).contains("secret-access-key"));
+
+    client
+        .set_url(
+            5,
+            "static",
+            "/5.dem",
+            "https://example.com/5.dem",
+            [0; 16],
+            "secret-api-key",
+        )
+        .await
+        .unwrap();
+    client
+        .upload_demo(
+            "test.dem".into(),
+            b"secret-demo-data".to_vec(),
+            "red".into(),
+            "blue".into(),
+            "secret-api-key".into(),
+        )
+        .await
+        .unwrap();
+    client.delete_demo(5, "secret-api-key").await.unwrap();
+    server.join().unwrap();
+
+    let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+    assert!(logs.contains("set_url"));
+    assert!(logs.contains("demo_id=5"));
+    assert!(!logs.contains("secret"), "{}", logs);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_stored_key() {
+    let (url, server) = test_server(vec![
+        http_response("200 OK", ""),
+        json_response("https://demos.tf/5"),
+        http_response("200 OK", ""),
+    ]);
+    let client = ApiClient::with_base_url(url).unwrap();
+
+    assert_eq!(
+        client.delete_demo_with_stored_key(5).await,
+        Err(Error::MissingKey)
+    );
+    assert_eq!(
+        client
+            .upload_demo_with_stored_key(
+                "test.dem".into(),
+                b"demo".to_vec(),
+                "red".into(),
+                "blue".into()
+            )
+            .await,
+        Err(Error::MissingKey)
     );
+
+    let client = client.with_access_key("stored-key");
+    client
+        .set_url_with_stored_key(5, "static", "/5.dem", "https://example.com/5.dem", [0; 16])
+        .await
+        .unwrap();
+    let id = client
+        .upload_demo_with_stored_key(
+            "test.dem".into(),
+            b"demo".to_vec(),
+            "red".into(),
+            "blue".into(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(id, DemoId(5));
+    client.delete_demo_with_stored_key(5).await.unwrap();
+
+    let requests = server.join().unwrap();
+    assert_eq!(requests.len(), 3);
+    for request in &requests {
+        assert!(request.contains("stored-key"), "{}", request);
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_retry() {
+    let retry = RetryConfig {
+        max_retries: 2,
+        base_delay: Duration::from_millis(1),
+        max_delay: Duration::from_millis(1),
+    };
+
+    let (url, server) = test_server(vec![
+        http_response("500 Internal Server Error", ""),
+        json_response("[]"),
+    ]);
+    let client = ApiClient::builder()
+        .base_url(url)
+        .retry(retry)
+        .build()
+        .unwrap();
+    client.search_users("foo").await.unwrap();
+    assert_eq!(server.join().unwrap().len(), 2);
+
+    let (url, server) = test_server(vec![
+        http_response("502 Bad Gateway", ""),
+        http_response("503 Service Unavailable", ""),
+        http_response("500 Internal Server Error", ""),
+    ]);
+    let client = ApiClient::builder()
+        .base_url(url)
+        .retry(retry)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        client.search_users("foo").await.unwrap_err(),
+        Error::ServerError { status: 500, .. }
+    ));
+    assert_eq!(server.join().unwrap().len(), 3);
+
+    let (url, server) = test_server(vec![
+        "HTTP/1.1 429 Too Many Requests\r\nretry-after: 120\r\ncontent-length: 0\r\nconnection: close\r\n\r\n".into(),
+    ]);
+    let client = ApiClient::builder()
+        .base_url(url)
+        .retry(retry)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        client.search_users("foo").await.unwrap_err(),
+        Error::RateLimited { retry_after: Some(retry_after) } if retry_after == Duration::from_secs(120)
+    ));
+    assert_eq!(server.join().unwrap().len(), 1);
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_server_error_body() {
+    let (url, server) = test_server(vec![http_response(
+        "500 Internal Server Error",
+        r#"{"error":"database unavailable"}"#,
+    )]);
+    let client = ApiClient::with_base_url(url).unwrap();
+    match client.get(1).await.unwrap_err() {
+        Error::ServerError { status, body } => {
+            assert_eq!(status, 500);
+            assert_eq!(body, r#"{"error":"database unavailable"}"#);
+        }
+        err => panic!("unexpected error {err:?}"),
+    }
+    server.join().unwrap();
 }
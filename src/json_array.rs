@@ -0,0 +1,139 @@
+use crate::Error;
+use serde::de::DeserializeOwned;
+
+/// Incremental parser for a json array, yielding the array items as soon as they are complete
+///
+/// Only the array itself is tracked by the parser, every item is parsed using `serde_json` once the
+/// end of the item has been found.
+#[derive(Debug, Default)]
+pub(crate) struct JsonArrayParser {
+    buffer: Vec<u8>,
+    /// Position in the buffer up to which the data has been scanned
+    position: usize,
+    /// Start of the current item in the buffer
+    item_start: Option<usize>,
+    depth: u32,
+    in_string: bool,
+    escaped: bool,
+    started: bool,
+    finished: bool,
+}
+
+impl JsonArrayParser {
+    /// Whether the end of the array has been reached
+    pub fn is_finished(&self) -> bool {
+        self.finished
+    }
+
+    /// Add data to the parser, returning all items completed by the data
+    pub fn feed<T: DeserializeOwned>(&mut self, data: &[u8]) -> Result<Vec<T>, Error> {
+        self.buffer.extend_from_slice(data);
+        let mut items = Vec::new();
+
+        while self.position < self.buffer.len() && !self.finished {
+            let byte = self.buffer[self.position];
+            let position = self.position;
+            self.position += 1;
+
+            if self.in_string {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => self.in_string = false,
+                    _ => {}
+                }
+                continue;
+            }
+
+            if byte.is_ascii_whitespace() {
+                continue;
+            }
+
+            if !self.started {
+                if byte != b'[' {
+                    return Err(invalid_array());
+                }
+                self.started = true;
+                self.depth = 1;
+                continue;
+            }
+
+            match byte {
+                b',' if self.depth == 1 => {
+                    let start = self.item_start.take().ok_or_else(invalid_array)?;
+                    items.push(self.parse_item(start, position)?);
+                }
+                b']' | b'}' if self.depth == 1 => {
+                    if byte == b'}' {
+                        return Err(invalid_array());
+                    }
+                    if let Some(start) = self.item_start.take() {
+                        items.push(self.parse_item(start, position)?);
+                    }
+                    self.depth = 0;
+                    self.finished = true;
+                }
+                b']' | b'}' => self.depth -= 1,
+                _ => {
+                    if self.item_start.is_none() {
+                        self.item_start = Some(position);
+                    }
+                    match byte {
+                        b'[' | b'{' => self.depth += 1,
+                        b'"' => self.in_string = true,
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        // drop the data for all completed items
+        let consumed = self.item_start.unwrap_or(self.position);
+        self.buffer.drain(..consumed);
+        self.position -= consumed;
+        self.item_start = self.item_start.map(|start| start - consumed);
+
+        Ok(items)
+    }
+
+    fn parse_item<T: DeserializeOwned>(&self, start: usize, end: usize) -> Result<T, Error> {
+        serde_json::from_slice(&self.buffer[start..end])
+            .map_err(|e| Error::InvalidResponse(e.to_string()))
+    }
+}
+
+fn invalid_array() -> Error {
+    Error::InvalidResponse("expected a json array".into())
+}
+
+#[test]
+fn test_parse_json_array() {
+    let json = br#" [ {"a": [1, 2], "b": "x,]}\"y"}, {"a": [], "b": ""} ] "#;
+
+    for chunk_size in 1..json.len() {
+        let mut parser = JsonArrayParser::default();
+        let mut items: Vec<serde_json::Value> = Vec::new();
+        for chunk in json.chunks(chunk_size) {
+            items.extend(parser.feed(chunk).unwrap());
+        }
+        assert!(parser.is_finished());
+        assert_eq!(
+            items,
+            vec![
+                serde_json::json!({"a": [1, 2], "b": "x,]}\"y"}),
+                serde_json::json!({"a": [], "b": ""}),
+            ]
+        );
+    }
+
+    let mut parser = JsonArrayParser::default();
+    assert!(parser.feed::<u32>(b"[]").unwrap().is_empty());
+    assert!(parser.is_finished());
+
+    let mut parser = JsonArrayParser::default();
+    assert_eq!(parser.feed::<u32>(b"[1, 2").unwrap(), vec![1]);
+    assert!(!parser.is_finished());
+
+    let mut parser = JsonArrayParser::default();
+    assert!(parser.feed::<u32>(br#"{"a": 1}"#).is_err());
+}
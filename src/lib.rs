@@ -1,45 +1,322 @@
 use bytes::Bytes;
-pub use client::ApiClient;
+#[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+pub use cache::CacheConfig;
+use client::body_stream;
+pub use client::{ApiClient, ApiClientBuilder, RequestOptions};
 use futures_util::{Stream, StreamExt};
 use md5::Context;
+pub use mock::{DemoApi, MockApiClient};
 use reqwest::StatusCode;
+#[cfg(not(target_arch = "wasm32"))]
+pub use retry::RetryConfig;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::{Map, Value};
 use std::borrow::Cow;
+use std::cmp::Ordering;
+use std::collections::{BTreeSet, HashMap};
 use std::fmt::{self, Debug, Display, Formatter};
+use std::hash::{Hash, Hasher};
 use std::io::Write;
+use std::str::FromStr;
+use std::time::Duration;
 pub use steamid_ng::SteamID;
+use steamid_ng::{AccountType, Instance, Universe};
 use thiserror::Error;
-use time::OffsetDateTime;
+#[cfg(feature = "time")]
+use time::{OffsetDateTime, UtcOffset};
 use tinyvec::TinyVec;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
 use tracing::{debug, error, instrument};
 
+#[cfg(all(feature = "cache", not(target_arch = "wasm32")))]
+mod cache;
 mod client;
+mod json_array;
+mod mock;
+#[cfg(not(target_arch = "wasm32"))]
+mod rate_limit;
+#[cfg(not(target_arch = "wasm32"))]
+mod retry;
+
+/// Point in time as used for the upload time of demos
+///
+/// Without the `time` feature, this is the number of seconds since the unix epoch instead.
+#[cfg(feature = "time")]
+pub type Timestamp = OffsetDateTime;
+/// Point in time as used for the upload time of demos
+///
+/// With the `time` feature, this is an `OffsetDateTime` instead.
+#[cfg(not(feature = "time"))]
+pub type Timestamp = i64;
 
 #[derive(Debug, Error)]
 #[non_exhaustive]
 pub enum Error {
     #[error("Invalid base url")]
     InvalidBaseUrl,
+    #[error("Invalid user agent")]
+    InvalidUserAgent,
     #[error("Request failed: {0}")]
     Request(reqwest::Error),
-    #[error("Invalid page requested")]
-    InvalidPage,
+    #[error("Invalid page {requested} requested: {reason}")]
+    InvalidPage {
+        requested: u32,
+        reason: &'static str,
+    },
     #[error("Invalid api key")]
     InvalidApiKey,
-    #[error("Hash mismatch")]
-    HashMisMatch,
-    #[error("Unknown server error {0}")]
-    ServerError(u16),
+    #[error("No key configured for the client")]
+    MissingKey,
+    #[error("Hash mismatch, expected {}", hex::encode(.expected))]
+    HashMisMatch {
+        /// The hash the demo should have
+        expected: [u8; 16],
+        /// The hash of the received data, `None` if the hash was calculated by the server
+        calculated: Option<[u8; 16]>,
+    },
+    #[error("Incomplete download, expected {expected} bytes but received {got}")]
+    IncompleteDownload {
+        /// The size of the demo as reported by the server
+        expected: u64,
+        /// The number of bytes received
+        got: u64,
+    },
+    #[error("Unknown server error {status}: {body}")]
+    ServerError { status: u16, body: String },
     #[error("Invalid response: {0}")]
     InvalidResponse(String),
     #[error("Demo {0} not found")]
-    DemoNotFound(u32),
+    DemoNotFound(DemoId),
     #[error("User {0} not found")]
-    UserNotFound(u32),
+    UserNotFound(UserId),
     #[error("Error while writing demo data")]
     Write(#[source] std::io::Error),
+    #[error("Error while reading demo data")]
+    Read(#[source] std::io::Error),
     #[error("Operation timed out")]
     TimeOut,
+    #[error("Invalid steam id {0}")]
+    InvalidSteamId(u64),
+    #[error("Invalid unix timestamp {0}")]
+    InvalidTimestamp(i64),
+    #[error("No demo id found in url {0}")]
+    InvalidDemoUrl(String),
+    #[error("Rate limited by the server")]
+    RateLimited {
+        /// How long to wait before sending a new request, if provided by the server
+        retry_after: Option<Duration>,
+    },
+    #[error("Demo has already been uploaded")]
+    DuplicateDemo {
+        /// The id of the existing demo, if provided by the server
+        existing: Option<DemoId>,
+    },
+    #[error("Uploader is blacklisted")]
+    Blacklisted,
+    #[error("Demo was rejected by the server: {0}")]
+    InvalidDemo(String),
+}
+
+/// Category of an [`Error`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// An invalid value was provided to the client, such as an invalid url or page
+    InvalidInput,
+    /// The provided api key was rejected
+    Unauthorized,
+    /// The requested demo or user doesn't exist
+    NotFound,
+    /// An uploaded demo was rejected by the server
+    Rejected,
+    /// The hash of a demo didn't match
+    HashMismatch,
+    /// Too many requests were sent to the server
+    RateLimited,
+    /// The request didn't complete in time
+    TimeOut,
+    /// The server failed to handle the request
+    Server,
+    /// The request failed to be send or received
+    Transport,
+    /// The server returned a response that couldn't be understood
+    InvalidResponse,
+    /// Demo data couldn't be read or written
+    Io,
+}
+
+impl Error {
+    /// The category of the error
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::InvalidBaseUrl
+            | Error::InvalidUserAgent
+            | Error::InvalidPage { .. }
+            | Error::InvalidSteamId(_)
+            | Error::InvalidTimestamp(_)
+            | Error::InvalidDemoUrl(_)
+            | Error::MissingKey => ErrorKind::InvalidInput,
+            Error::InvalidApiKey | Error::Blacklisted => ErrorKind::Unauthorized,
+            Error::DemoNotFound(_) | Error::UserNotFound(_) => ErrorKind::NotFound,
+            Error::DuplicateDemo { .. } | Error::InvalidDemo(_) => ErrorKind::Rejected,
+            Error::HashMisMatch { .. } => ErrorKind::HashMismatch,
+            Error::RateLimited { .. } => ErrorKind::RateLimited,
+            Error::TimeOut => ErrorKind::TimeOut,
+            Error::ServerError { .. } => ErrorKind::Server,
+            Error::Request(e) if e.status() == Some(StatusCode::NOT_FOUND) => ErrorKind::NotFound,
+            Error::Request(_) | Error::IncompleteDownload { .. } => ErrorKind::Transport,
+            Error::InvalidResponse(_) => ErrorKind::InvalidResponse,
+            Error::Write(_) | Error::Read(_) => ErrorKind::Io,
+        }
+    }
+
+    /// Whether sending the request again might succeed, true for timeouts, server errors and rate limiting
+    ///
+    /// These are the same errors that are retried when a [`RetryConfig`] is configured.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self.kind(),
+            ErrorKind::TimeOut | ErrorKind::Server | ErrorKind::RateLimited
+        )
+    }
+
+    /// Whether the requested demo or user doesn't exist
+    pub fn is_not_found(&self) -> bool {
+        self.kind() == ErrorKind::NotFound
+    }
+}
+
+#[test]
+fn test_error_kind() {
+    assert_eq!(ErrorKind::NotFound, Error::DemoNotFound(DemoId(1)).kind());
+    assert!(Error::UserNotFound(UserId(1)).is_not_found());
+    assert!(!Error::UserNotFound(UserId(1)).is_retryable());
+    assert!(Error::TimeOut.is_retryable());
+    assert!(Error::RateLimited { retry_after: None }.is_retryable());
+    assert!(Error::ServerError {
+        status: 503,
+        body: String::new()
+    }
+    .is_retryable());
+    assert_eq!(ErrorKind::Unauthorized, Error::InvalidApiKey.kind());
+    assert!(!Error::InvalidApiKey.is_retryable());
+    assert_eq!(ErrorKind::InvalidInput, Error::InvalidSteamId(0).kind());
+    assert_eq!(
+        ErrorKind::Rejected,
+        Error::InvalidDemo(String::new()).kind()
+    );
+}
+
+/// Errors are compared by their kind and payload, transport errors from `reqwest` are always considered equal
+/// and io errors are compared by their `ErrorKind`
+impl PartialEq for Error {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                Error::InvalidPage {
+                    requested: a_requested,
+                    reason: a_reason,
+                },
+                Error::InvalidPage {
+                    requested: b_requested,
+                    reason: b_reason,
+                },
+            ) => a_requested == b_requested && a_reason == b_reason,
+            (
+                Error::HashMisMatch {
+                    expected: a_expected,
+                    calculated: a_calculated,
+                },
+                Error::HashMisMatch {
+                    expected: b_expected,
+                    calculated: b_calculated,
+                },
+            ) => a_expected == b_expected && a_calculated == b_calculated,
+            (
+                Error::IncompleteDownload {
+                    expected: a_expected,
+                    got: a_got,
+                },
+                Error::IncompleteDownload {
+                    expected: b_expected,
+                    got: b_got,
+                },
+            ) => a_expected == b_expected && a_got == b_got,
+            (
+                Error::ServerError {
+                    status: a_status,
+                    body: a_body,
+                },
+                Error::ServerError {
+                    status: b_status,
+                    body: b_body,
+                },
+            ) => a_status == b_status && a_body == b_body,
+            (Error::InvalidResponse(a), Error::InvalidResponse(b)) => a == b,
+            (Error::DemoNotFound(a), Error::DemoNotFound(b)) => a == b,
+            (Error::UserNotFound(a), Error::UserNotFound(b)) => a == b,
+            (Error::Write(a), Error::Write(b)) | (Error::Read(a), Error::Read(b)) => {
+                a.kind() == b.kind()
+            }
+            (Error::InvalidSteamId(a), Error::InvalidSteamId(b)) => a == b,
+            (Error::InvalidTimestamp(a), Error::InvalidTimestamp(b)) => a == b,
+            (Error::InvalidDemoUrl(a), Error::InvalidDemoUrl(b)) => a == b,
+            (
+                Error::RateLimited {
+                    retry_after: a_retry_after,
+                },
+                Error::RateLimited {
+                    retry_after: b_retry_after,
+                },
+            ) => a_retry_after == b_retry_after,
+            (Error::DuplicateDemo { existing: a }, Error::DuplicateDemo { existing: b }) => a == b,
+            (Error::InvalidDemo(a), Error::InvalidDemo(b)) => a == b,
+            (Error::Request(_), Error::Request(_))
+            | (Error::InvalidBaseUrl, Error::InvalidBaseUrl)
+            | (Error::InvalidUserAgent, Error::InvalidUserAgent)
+            | (Error::InvalidApiKey, Error::InvalidApiKey)
+            | (Error::MissingKey, Error::MissingKey)
+            | (Error::TimeOut, Error::TimeOut)
+            | (Error::Blacklisted, Error::Blacklisted) => true,
+            _ => false,
+        }
+    }
+}
+
+#[test]
+fn test_error_eq() {
+    assert_eq!(
+        Error::DemoNotFound(DemoId(999)),
+        Error::DemoNotFound(DemoId(999))
+    );
+    assert_ne!(
+        Error::DemoNotFound(DemoId(999)),
+        Error::DemoNotFound(DemoId(1))
+    );
+    assert_ne!(
+        Error::DemoNotFound(DemoId(1)),
+        Error::UserNotFound(UserId(1))
+    );
+    assert_eq!(Error::TimeOut, Error::TimeOut);
+    assert_ne!(Error::TimeOut, Error::InvalidApiKey);
+    assert_eq!(
+        Error::Write(std::io::ErrorKind::NotFound.into()),
+        Error::Write(std::io::Error::new(std::io::ErrorKind::NotFound, "missing"))
+    );
+    assert_ne!(
+        Error::Write(std::io::ErrorKind::NotFound.into()),
+        Error::Write(std::io::ErrorKind::PermissionDenied.into())
+    );
+    assert_ne!(
+        Error::ServerError {
+            status: 500,
+            body: String::new()
+        },
+        Error::ServerError {
+            status: 502,
+            body: String::new()
+        }
+    );
 }
 
 impl From<reqwest::Error> for Error {
@@ -49,46 +326,378 @@ impl From<reqwest::Error> for Error {
         } else {
             match error.status() {
                 Some(StatusCode::UNAUTHORIZED) => Error::InvalidApiKey,
-                Some(StatusCode::PRECONDITION_FAILED) => Error::HashMisMatch,
-                Some(status) if status.is_server_error() => Error::ServerError(status.as_u16()),
+                Some(StatusCode::TOO_MANY_REQUESTS) => Error::RateLimited { retry_after: None },
+                Some(status) if status.is_server_error() => Error::ServerError {
+                    status: status.as_u16(),
+                    body: String::new(),
+                },
                 _ => Error::Request(error),
             }
         }
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
+/// Error returned when parsing an unknown value into one of the enums of this crate
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid {kind} \"{value}\", expected one of {expected}")]
+pub struct ParseEnumError {
+    kind: &'static str,
+    value: String,
+    expected: &'static str,
+}
+
+impl ParseEnumError {
+    fn new(kind: &'static str, value: &str, expected: &'static str) -> Self {
+        ParseEnumError {
+            kind,
+            value: value.into(),
+            expected,
+        }
+    }
+
+    /// The value that failed to parse
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Id of a demo
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct DemoId(pub u32);
+
+impl From<u32> for DemoId {
+    fn from(id: u32) -> Self {
+        DemoId(id)
+    }
+}
+
+impl From<DemoId> for u32 {
+    fn from(id: DemoId) -> Self {
+        id.0
+    }
+}
+
+impl Display for DemoId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+/// Id of a user
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct UserId(pub u32);
+
+impl From<u32> for UserId {
+    fn from(id: u32) -> Self {
+        UserId(id)
+    }
+}
+
+impl From<UserId> for u32 {
+    fn from(id: UserId) -> Self {
+        id.0
+    }
+}
+
+impl Display for UserId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 /// Data of an uploaded demo
 pub struct Demo {
-    pub id: u32,
+    pub id: DemoId,
     pub url: String,
     pub name: String,
     pub server: String,
+    /// Length of the demo in seconds
     pub duration: u16,
     pub nick: String,
     pub map: String,
-    #[serde(with = "time::serde::timestamp")]
-    pub time: OffsetDateTime,
+    /// Upload time of the demo, always in UTC
+    ///
+    /// Demos received from the api always have a UTC offset, [`Demo::time_utc`] converts the time for
+    /// demos constructed with a different offset. Without the `time` feature, this is a unix timestamp.
+    #[cfg_attr(feature = "time", serde(with = "time::serde::timestamp"))]
+    pub time: Timestamp,
     pub red: String,
     pub blue: String,
     pub red_score: u8,
     pub blue_score: u8,
     pub player_count: u8,
     pub uploader: UserRef,
-    #[serde(deserialize_with = "hex_to_digest")]
+    #[serde(deserialize_with = "hex_to_digest", serialize_with = "digest_to_hex")]
     pub hash: [u8; 16],
     pub backend: String,
     pub path: String,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     /// Demos listed using `ApiClient::list` will not have any players set, use `get_players` to automatically
     /// load the players when not set
     pub players: Option<Vec<Player>>,
+    /// Number of chat messages in the demo, if provided by the api
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chat_count: Option<u32>,
+    /// Whether the demo requires an access key, public if not provided by the api
+    #[serde(default, skip_serializing_if = "DemoVisibility::is_public")]
+    pub visibility: DemoVisibility,
+    /// Any fields returned by the api that aren't supported by this crate yet
+    #[serde(flatten, skip_serializing_if = "Map::is_empty")]
+    pub extra: Map<String, Value>,
+}
+
+/// Location where the demo file is stored
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Storage {
+    /// Name of the storage backend
+    pub backend: String,
+    /// Path of the demo file within the backend
+    pub path: String,
+    /// Url the demo file can be downloaded from
+    pub url: String,
+}
+
+/// Who can access a demo
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum DemoVisibility {
+    /// Accessible and listed for everyone
+    #[default]
+    Public,
+    /// Only accessible with an access key
+    Private,
+    /// Accessible for everyone with the demo id, but not listed
+    Unlisted,
+}
+
+impl DemoVisibility {
+    pub fn is_public(&self) -> bool {
+        *self == DemoVisibility::Public
+    }
+}
+
+/// Demos are compared by their id only
+impl PartialEq for Demo {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Demo {}
+
+/// Short summary of the match, formatted as `#{id} {map}: {red} {red_score}-{blue_score} {blue} ({duration}s)`
+impl Display for Demo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "#{} {}: {} {}-{} {} ({}s)",
+            self.id, self.map, self.red, self.red_score, self.blue_score, self.blue, self.duration
+        )
+    }
+}
+
+impl Hash for Demo {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+    }
 }
 
 impl Demo {
+    /// The location where the demo file is stored
+    pub fn storage(&self) -> Storage {
+        Storage {
+            backend: self.backend.clone(),
+            path: self.path.clone(),
+            url: self.url.clone(),
+        }
+    }
+
+    /// Whether the demo can have chat messages
+    ///
+    /// Only returns `false` if the api reported that the demo has no chat messages, in which case requesting the chat
+    /// can be skipped.
+    pub fn has_chat(&self) -> bool {
+        self.chat_count.map_or(true, |count| count > 0)
+    }
+
+    /// Whether an access key is required to access the demo
+    pub fn is_private(&self) -> bool {
+        self.visibility == DemoVisibility::Private
+    }
+
+    /// The normalized name of the map the demo was recorded on
+    pub fn map_name(&self) -> MapName {
+        MapName::new(&self.map)
+    }
+
+    /// The upload time of the demo in UTC
+    #[cfg(feature = "time")]
+    pub fn time_utc(&self) -> OffsetDateTime {
+        self.time.to_offset(UtcOffset::UTC)
+    }
+
+    /// Time since the demo was uploaded, zero for upload times in the future
+    ///
+    /// The current time isn't available on wasm.
+    #[cfg(all(feature = "time", not(target_arch = "wasm32")))]
+    pub fn age(&self) -> Duration {
+        (OffsetDateTime::now_utc() - self.time)
+            .try_into()
+            .unwrap_or_default()
+    }
+
+    /// Time since the demo was uploaded, zero for upload times in the future
+    ///
+    /// The current time isn't available on wasm.
+    #[cfg(all(not(feature = "time"), not(target_arch = "wasm32")))]
+    pub fn age(&self) -> Duration {
+        let uploaded = std::time::UNIX_EPOCH + Duration::from_secs(self.time.max(0) as u64);
+        std::time::SystemTime::now()
+            .duration_since(uploaded)
+            .unwrap_or_default()
+    }
+
+    /// The length of the demo
+    pub fn duration_as(&self) -> Duration {
+        Duration::from_secs(u64::from(self.duration))
+    }
+
+    /// The team that won the match, or `None` if the match ended in a draw
+    pub fn winner(&self) -> Option<Team> {
+        match self.red_score.cmp(&self.blue_score) {
+            Ordering::Greater => Some(Team::Red),
+            Ordering::Less => Some(Team::Blue),
+            Ordering::Equal => None,
+        }
+    }
+
+    /// The final score of a team
+    pub fn score(&self, team: Team) -> u8 {
+        match team {
+            Team::Red => self.red_score,
+            Team::Blue => self.blue_score,
+        }
+    }
+
+    /// Whether the match ended with both teams having the same score
+    pub fn is_draw(&self) -> bool {
+        self.red_score == self.blue_score
+    }
+
+    /// The loaded players that played on a team
+    ///
+    /// This only includes players already stored in the demo, use `get_players` to load them if needed
+    pub fn players_on_team(&self, team: Team) -> impl Iterator<Item = &Player> {
+        self.players
+            .iter()
+            .flatten()
+            .filter(move |player| player.team == team)
+    }
+
+    /// The loaded players that played a class
+    ///
+    /// This only includes players already stored in the demo, use `get_players` to load them if needed
+    pub fn players_of_class(&self, class: Class) -> impl Iterator<Item = &Player> {
+        self.players
+            .iter()
+            .flatten()
+            .filter(move |player| player.class == class)
+    }
+
+    /// Whether any of the loaded players played a class
+    ///
+    /// The api doesn't support filtering demos by class, so this can be used to filter listed demos
+    /// after loading their players with `get_players`. Besides the main class of a player, the class
+    /// breakdown is also checked if the api provided one.
+    pub fn had_class(&self, class: Class) -> bool {
+        self.players.iter().flatten().any(|player| {
+            player.class == class || player.classes.iter().any(|played| played.class == class)
+        })
+    }
+
+    /// Combined stats of a team, or `None` if the players aren't loaded
+    pub fn team_stats(&self, team: Team) -> Option<TeamStats> {
+        self.players
+            .as_deref()
+            .map(|players| TeamStats::from_players(players, team))
+    }
+
+    /// Compare the scores and player stats of this demo with another demo
+    ///
+    /// All deltas are the value of `other` minus the value of this demo. Player stats are only compared
+    /// for players that are loaded in both demos, matched by steam id.
+    pub fn diff(&self, other: &Demo) -> DemoDiff {
+        let players =
+            self.players
+                .iter()
+                .flatten()
+                .filter_map(|player| {
+                    let other_player =
+                        other.players.iter().flatten().find(|other_player| {
+                            other_player.user.steam_id == player.user.steam_id
+                        })?;
+                    Some(PlayerStatDelta {
+                        steam_id: player.user.steam_id,
+                        kills: i16::from(other_player.kills) - i16::from(player.kills),
+                        assists: i16::from(other_player.assists) - i16::from(player.assists),
+                        deaths: i16::from(other_player.deaths) - i16::from(player.deaths),
+                    })
+                })
+                .collect();
+
+        DemoDiff {
+            red_score_delta: i16::from(other.red_score) - i16::from(self.red_score),
+            blue_score_delta: i16::from(other.blue_score) - i16::from(self.blue_score),
+            players,
+        }
+    }
+
+    /// The name of the demo, made safe to use as a file name
+    ///
+    /// Path separators, characters not allowed in file names on Windows and control characters are replaced,
+    /// leading dots are removed and names reserved on Windows are escaped. The name always ends in `.dem`.
+    pub fn safe_filename(&self) -> String {
+        let name: String = self
+            .name
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | '<' | '>' | ':' | '"' | '|' | '?' | '*' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .collect();
+        let name = name.trim_start_matches('.').trim_end_matches(['.', ' ']);
+        let mut name = match name {
+            "" => "demo".to_string(),
+            name => name.to_string(),
+        };
+
+        let stem = name.split('.').next().unwrap_or_default().trim_end();
+        let reserved = matches!(
+            stem.to_ascii_uppercase().as_str(),
+            "CON" | "PRN" | "AUX" | "NUL"
+        ) || (stem.len() == 4
+            && ["COM", "LPT"].iter().any(|prefix| {
+                stem.get(..3)
+                    .is_some_and(|s| s.eq_ignore_ascii_case(prefix))
+            })
+            && stem.as_bytes()[3].is_ascii_digit());
+        if reserved {
+            name.insert(0, '_');
+        }
+
+        if !name.to_ascii_lowercase().ends_with(".dem") {
+            name.push_str(".dem");
+        }
+        name
+    }
+
     /// Return either the stored players info or get the players from the api
-    #[instrument]
+    #[instrument(skip(self, client), fields(demo_id = self.id.0))]
     pub async fn get_players(&self, client: &ApiClient) -> Result<Cow<'_, [Player]>, Error> {
         match &self.players {
             Some(players) => Ok(Cow::Borrowed(players.as_slice())),
@@ -100,12 +709,16 @@ impl Demo {
     }
 
     /// Download a demo, returning a stream of chunks
-    #[instrument]
+    #[instrument(skip(self, client), fields(demo_id = self.id.0))]
     pub async fn download(
         &self,
         client: &ApiClient,
     ) -> Result<impl Stream<Item = Result<Bytes, Error>>, Error> {
-        debug!(id = self.id, url = display(&self.url), "starting download");
+        debug!(
+            id = self.id.0,
+            url = display(&self.url),
+            "starting download"
+        );
         Ok(client
             .download_demo(&self.url, self.duration)
             .await?
@@ -114,19 +727,193 @@ impl Demo {
     }
 
     /// Download a demo and save it to a writer, verifying the md5 hash in the process
-    #[instrument(skip(target))]
-    pub async fn save<W: Write>(&self, client: &ApiClient, mut target: W) -> Result<(), Error> {
-        debug!(id = self.id, url = display(&self.url), "starting download");
-        let mut response = client.download_demo(&self.url, self.duration).await?;
+    ///
+    /// Demos without a known hash (an all-zero hash) are saved without verification
+    pub async fn save<W: Write>(&self, client: &ApiClient, target: W) -> Result<(), Error> {
+        self.save_verified(client, target).await?;
+        Ok(())
+    }
 
+    /// Download a demo and save it to an async writer, verifying the md5 hash in the process
+    ///
+    /// Demos without a known hash (an all-zero hash) are saved without verification
+    #[instrument(skip(self, client, target), fields(demo_id = self.id.0))]
+    pub async fn save_async<W: AsyncWrite + Unpin>(
+        &self,
+        client: &ApiClient,
+        mut target: W,
+    ) -> Result<(), Error> {
+        debug!(
+            id = self.id.0,
+            url = display(&self.url),
+            "starting download"
+        );
+        let response = client.download_demo(&self.url, self.duration).await?;
+        let total = response.content_length();
         let mut context = Context::new();
+        let mut downloaded = 0;
+
+        let mut body = body_stream(response);
 
-        while let Some(chunk) = response.chunk().await? {
+        while let Some(chunk) = body.next().await.transpose()? {
+            context.consume(&chunk);
+            target.write_all(&chunk).await.map_err(Error::Write)?;
+            downloaded += chunk.len() as u64;
+        }
+        target.flush().await.map_err(Error::Write)?;
+
+        check_length(total, downloaded)?;
+
+        self.verify_hash(context.compute().0)?;
+        Ok(())
+    }
+
+    /// Download a demo into memory, verifying the md5 hash like `save` does
+    pub async fn download_bytes(&self, client: &ApiClient) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        self.save_verified(client, &mut data).await?;
+        Ok(data)
+    }
+
+    /// Download a demo and save it to a writer, returning the verified md5 hash of the demo
+    ///
+    /// Demos without a known hash (an all-zero hash) are saved without verification
+    pub async fn save_verified<W: Write>(
+        &self,
+        client: &ApiClient,
+        target: W,
+    ) -> Result<[u8; 16], Error> {
+        let calculated = self.download_to(client, target, |_, _| {}).await?;
+        self.verify_hash(calculated)
+    }
+
+    /// Download a demo and save it to a writer without verifying the md5 hash
+    pub async fn save_unverified<W: Write>(
+        &self,
+        client: &ApiClient,
+        target: W,
+    ) -> Result<(), Error> {
+        self.download_to(client, target, |_, _| {}).await?;
+        Ok(())
+    }
+
+    /// Download a demo and save it to a writer, verifying the md5 hash in the process
+    ///
+    /// After every received chunk, `progress` is called with the number of bytes downloaded so far
+    /// and the total size of the demo, if the server provided one.
+    pub async fn save_with_progress<W: Write, F: FnMut(u64, Option<u64>)>(
+        &self,
+        client: &ApiClient,
+        target: W,
+        progress: F,
+    ) -> Result<(), Error> {
+        let calculated = self.download_to(client, target, progress).await?;
+        self.verify_hash(calculated)?;
+        Ok(())
+    }
+
+    /// Download the demo to a writer, returning the md5 hash of the downloaded data
+    #[instrument(skip(self, client, target, progress), fields(demo_id = self.id.0))]
+    async fn download_to<W: Write, F: FnMut(u64, Option<u64>)>(
+        &self,
+        client: &ApiClient,
+        mut target: W,
+        mut progress: F,
+    ) -> Result<[u8; 16], Error> {
+        debug!(
+            id = self.id.0,
+            url = display(&self.url),
+            "starting download"
+        );
+        let response = client.download_demo(&self.url, self.duration).await?;
+        let total = response.content_length();
+
+        let mut context = Context::new();
+        let mut downloaded = 0;
+
+        let mut body = body_stream(response);
+
+        while let Some(chunk) = body.next().await.transpose()? {
             context.consume(&chunk);
             target.write_all(&chunk).map_err(Error::Write)?;
+            downloaded += chunk.len() as u64;
+            progress(downloaded, total);
+        }
+
+        check_length(total, downloaded)?;
+        Ok(context.compute().0)
+    }
+
+    /// Continue an interrupted download, appending the rest of the demo to a writer
+    ///
+    /// `already_have` is the number of bytes of the demo that were already written to the target.
+    /// Because those bytes aren't downloaded again, the md5 hash can only be verified if the hash state
+    /// of the existing data is provided with [`ResumeVerification::Verify`], otherwise the downloaded
+    /// data is not verified at all.
+    ///
+    /// If the server doesn't support range requests the full demo is downloaded again, in which case
+    /// only the missing part is written to the target and the hash is always verified.
+    ///
+    /// If `already_have` is the full length of the demo, the download is already complete and only
+    /// the provided hash state is verified.
+    #[instrument(skip(self, client, target), fields(demo_id = self.id.0))]
+    pub async fn resume_download<W: Write>(
+        &self,
+        client: &ApiClient,
+        mut target: W,
+        already_have: u64,
+        verification: ResumeVerification,
+    ) -> Result<(), Error> {
+        debug!(
+            id = self.id.0,
+            url = display(&self.url),
+            already_have,
+            "resuming download"
+        );
+        let response = client
+            .download_demo_range(&self.url, self.duration, already_have)
+            .await?;
+
+        if response.status() == StatusCode::RANGE_NOT_SATISFIABLE {
+            debug!("demo was already fully downloaded");
+            if let ResumeVerification::Verify(context) = verification {
+                self.verify_hash(context.compute())?;
+            }
+            return Ok(());
+        }
+
+        let (mut context, mut skip) = if response.status() == StatusCode::PARTIAL_CONTENT {
+            match verification {
+                ResumeVerification::Verify(context) => (Some(context), 0),
+                ResumeVerification::SkipVerification => (None, 0),
+            }
+        } else {
+            debug!("server doesn't support range requests, downloading full demo");
+            (Some(Md5Context::new()), already_have)
+        };
+
+        let mut body = body_stream(response);
+
+        while let Some(chunk) = body.next().await.transpose()? {
+            if let Some(context) = context.as_mut() {
+                context.consume(&chunk);
+            }
+            let offset = skip.min(chunk.len() as u64) as usize;
+            skip -= offset as u64;
+            target.write_all(&chunk[offset..]).map_err(Error::Write)?;
         }
 
-        let calculated = context.compute().0;
+        if let Some(context) = context {
+            self.verify_hash(context.compute())?;
+        }
+        Ok(())
+    }
+
+    fn verify_hash(&self, calculated: [u8; 16]) -> Result<[u8; 16], Error> {
+        if self.hash == [0; 16] {
+            debug!("no hash known for demo, skipping verification");
+            return Ok(calculated);
+        }
 
         if calculated != self.hash {
             error!(
@@ -134,24 +921,131 @@ impl Demo {
                 expected = display(hex::encode(self.hash)),
                 "hash mismatch"
             );
-            return Err(Error::HashMisMatch);
+            return Err(Error::HashMisMatch {
+                expected: self.hash,
+                calculated: Some(calculated),
+            });
+        }
+        Ok(calculated)
+    }
+}
+
+/// Check that the full demo was received, if the server reported the size of the demo
+fn check_length(expected: Option<u64>, got: u64) -> Result<(), Error> {
+    match expected {
+        Some(expected) if expected != got => {
+            error!(expected, got, "incomplete download");
+            Err(Error::IncompleteDownload { expected, got })
         }
+        _ => Ok(()),
+    }
+}
+
+#[test]
+fn test_check_length() {
+    assert!(check_length(None, 10).is_ok());
+    assert!(check_length(Some(10), 10).is_ok());
+    assert_eq!(
+        Err(Error::IncompleteDownload {
+            expected: 10,
+            got: 4
+        }),
+        check_length(Some(10), 4)
+    );
+}
+
+/// How to verify the hash of a resumed download
+///
+/// Since the already downloaded part of the demo isn't re-read when resuming, the md5 hash of the full
+/// demo can only be verified when the hash state of that part is provided.
+#[derive(Clone, Debug)]
+pub enum ResumeVerification {
+    /// Verify the full demo using the hash state of the already downloaded bytes
+    ///
+    /// The context should have consumed exactly the bytes that were already written.
+    Verify(Md5Context),
+    /// Don't verify the hash of the demo
+    SkipVerification,
+}
+
+/// Incremental md5 hash state, used to verify resumed downloads
+///
+/// The already downloaded part of a demo can be added with [`Md5Context::consume`] or by writing to the context.
+///
+/// # Example
+///
+/// ```rust
+/// use demostf_client::{Md5Context, ResumeVerification};
+///
+/// let already_downloaded = b"the start of the demo";
+/// let mut context = Md5Context::new();
+/// context.consume(already_downloaded);
+/// let verification = ResumeVerification::Verify(context);
+/// ```
+#[derive(Clone)]
+pub struct Md5Context(Context);
+
+impl Md5Context {
+    pub fn new() -> Self {
+        Md5Context(Context::new())
+    }
+
+    /// Add data to the hash
+    pub fn consume(&mut self, data: impl AsRef<[u8]>) {
+        self.0.consume(data);
+    }
+
+    /// The md5 hash of all consumed data
+    pub(crate) fn compute(self) -> [u8; 16] {
+        self.0.compute().0
+    }
+}
+
+impl Default for Md5Context {
+    fn default() -> Self {
+        Md5Context::new()
+    }
+}
+
+impl Debug for Md5Context {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_str("Md5Context")
+    }
+}
+
+impl Write for Md5Context {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.consume(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
         Ok(())
     }
 }
 
+/// A single page of demos returned by `ApiClient::list_page`
+#[derive(Clone, Debug)]
+pub struct DemoPage {
+    pub demos: Vec<Demo>,
+    /// The requested page, starting at 1
+    pub page: u32,
+    /// Whether a full page was returned, indicating that the next page might contain more demos
+    pub has_more: bool,
+}
+
 /// Reference to a user, either contains the full user information or only the user id
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(untagged)]
 pub enum UserRef {
     User(User),
-    Id(u32),
+    Id(UserId),
 }
 
 impl UserRef {
     /// Id of the user
     #[must_use]
-    pub fn id(&self) -> u32 {
+    pub fn id(&self) -> UserId {
         match self {
             UserRef::Id(id) | UserRef::User(User { id, .. }) => *id,
         }
@@ -167,46 +1061,187 @@ impl UserRef {
     }
 
     /// Return either the stored user info or get the user information from the api
-    #[instrument]
+    #[instrument(skip(self, client), fields(user_id = self.id().0))]
     pub async fn resolve(&self, client: &ApiClient) -> Result<Cow<'_, User>, Error> {
         match self {
             UserRef::User(ref user) => Ok(Cow::Borrowed(user)),
             UserRef::Id(id) => Ok(Cow::Owned(client.get_user(*id).await?)),
         }
     }
+
+    /// Resolve multiple user references, returning the users in the same order as the references
+    ///
+    /// Every unresolved user is only requested once, even if it's referenced multiple times
+    #[instrument(skip(refs, client))]
+    pub async fn resolve_many(refs: &[UserRef], client: &ApiClient) -> Result<Vec<User>, Error> {
+        let mut users: HashMap<UserId, User> = refs
+            .iter()
+            .filter_map(UserRef::user)
+            .map(|user| (user.id, user.clone()))
+            .collect();
+        let missing: BTreeSet<UserId> = refs
+            .iter()
+            .map(UserRef::id)
+            .filter(|id| !users.contains_key(id))
+            .collect();
+
+        for user in client.get_users(missing).await? {
+            users.insert(user.id, user);
+        }
+
+        refs.iter()
+            .map(|user_ref| {
+                users
+                    .get(&user_ref.id())
+                    .cloned()
+                    .ok_or(Error::UserNotFound(user_ref.id()))
+            })
+            .collect()
+    }
 }
 
 /// User data
-#[derive(Clone, Debug, Deserialize)]
+///
+/// Users are compared by their id and steam id, ignoring the name
+#[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct User {
-    pub id: u32,
-    #[serde(rename = "steamid")]
+    pub id: UserId,
+    #[serde(rename = "steamid", serialize_with = "serialize_steam_id")]
     pub steam_id: SteamID,
     pub name: String,
+    /// Url of the user's steam avatar, if provided by the api
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+}
+
+impl User {
+    /// The 64 bit form of the user's steam id
+    #[must_use]
+    pub fn steam_id64(&self) -> u64 {
+        u64::from(self.steam_id)
+    }
+
+    /// Link to the user's steam community profile
+    #[must_use]
+    pub fn profile_url(&self) -> String {
+        format!("https://steamcommunity.com/profiles/{}", self.steam_id64())
+    }
+}
+
+impl PartialEq for User {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id && self.steam_id == other.steam_id
+    }
+}
+
+impl Eq for User {}
+
+impl Hash for User {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.id.hash(state);
+        self.steam_id.hash(state);
+    }
 }
 
 /// Data of a player in a demo
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
 pub struct Player {
     #[serde(rename = "id")]
     pub player_id: u32,
     #[serde(flatten)]
-    #[serde(deserialize_with = "deserialize_nested_user")]
+    #[serde(
+        deserialize_with = "deserialize_nested_user",
+        serialize_with = "serialize_nested_user"
+    )]
     pub user: User,
     pub team: Team,
     /// If a player has played multiple classes, the class which the user spawned the most as is taken
     pub class: Class,
+    /// Time played as each class, empty if the api didn't provide a class breakdown
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub classes: Vec<ClassPlaytime>,
     pub kills: u8,
     pub assists: u8,
     pub deaths: u8,
 }
 
-#[derive(Clone, Debug, Deserialize)]
+impl Player {
+    /// Kill/death ratio of the player
+    ///
+    /// Players without any deaths have a ratio equal to their number of kills
+    pub fn kdr(&self) -> f32 {
+        f32::from(self.kills) / f32::from(self.deaths.max(1))
+    }
+
+    /// Ratio of kills and assists to deaths
+    ///
+    /// Players without any deaths have a ratio equal to their number of kills and assists
+    pub fn kda(&self) -> f32 {
+        (f32::from(self.kills) + f32::from(self.assists)) / f32::from(self.deaths.max(1))
+    }
+
+    /// Combined number of kills and assists
+    pub fn total_score(&self) -> u32 {
+        u32::from(self.kills) + u32::from(self.assists)
+    }
+}
+
+/// Combined kills, assists and deaths of all players in a team
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TeamStats {
+    pub kills: u32,
+    pub assists: u32,
+    pub deaths: u32,
+}
+
+impl TeamStats {
+    /// Sum the stats of all players in a team
+    pub fn from_players(players: &[Player], team: Team) -> Self {
+        players.iter().filter(|player| player.team == team).fold(
+            TeamStats::default(),
+            |stats, player| TeamStats {
+                kills: stats.kills + u32::from(player.kills),
+                assists: stats.assists + u32::from(player.assists),
+                deaths: stats.deaths + u32::from(player.deaths),
+            },
+        )
+    }
+}
+
+/// Differences between two demos, as returned by [`Demo::diff`]
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DemoDiff {
+    pub red_score_delta: i16,
+    pub blue_score_delta: i16,
+    /// Stat differences for the players loaded in both demos
+    pub players: Vec<PlayerStatDelta>,
+}
+
+/// Differences in the stats of a player between two demos
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlayerStatDelta {
+    pub steam_id: SteamID,
+    pub kills: i16,
+    pub assists: i16,
+    pub deaths: i16,
+}
+
+/// Time a player has spent playing as a class
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+pub struct ClassPlaytime {
+    pub class: Class,
+    /// Playtime in seconds
+    pub time: u32,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
 struct NestedPlayerUser {
-    user_id: u32,
-    #[serde(rename = "steamid")]
+    user_id: UserId,
+    #[serde(rename = "steamid", serialize_with = "serialize_steam_id")]
     steam_id: SteamID,
     name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    avatar: Option<String>,
 }
 
 fn deserialize_nested_user<'de, D>(deserializer: D) -> Result<User, D::Error>
@@ -218,11 +1253,33 @@ where
         id: nested.user_id,
         steam_id: nested.steam_id,
         name: nested.name,
+        avatar: nested.avatar,
     })
 }
 
+fn serialize_nested_user<S>(user: &User, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    NestedPlayerUser {
+        user_id: user.id,
+        steam_id: user.steam_id,
+        name: user.name.clone(),
+        avatar: user.avatar.clone(),
+    }
+    .serialize(serializer)
+}
+
+/// Serializes a steam id as a string of the 64 bit id, as returned by the api.
+fn serialize_steam_id<S>(steam_id: &SteamID, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_str(&u64::from(*steam_id))
+}
+
 /// Player team, red or blue
-#[derive(Clone, Copy, Debug, Deserialize, PartialOrd, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialOrd, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Team {
     Red,
@@ -230,7 +1287,7 @@ pub enum Team {
 }
 
 /// Player class
-#[derive(Clone, Copy, Debug, Deserialize, PartialOrd, PartialEq)]
+#[derive(Clone, Copy, Debug, Deserialize, Serialize, PartialOrd, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Class {
     Scout,
@@ -244,6 +1301,142 @@ pub enum Class {
     Spy,
 }
 
+impl Class {
+    /// All classes, in the order of the in-game class selection
+    pub fn all() -> [Class; 9] {
+        [
+            Class::Scout,
+            Class::Soldier,
+            Class::Pyro,
+            Class::Demoman,
+            Class::HeavyWeapons,
+            Class::Engineer,
+            Class::Medic,
+            Class::Sniper,
+            Class::Spy,
+        ]
+    }
+
+    /// The role of the class, following the grouping of the in-game class selection
+    pub fn role(&self) -> Role {
+        match self {
+            Class::Scout | Class::Soldier | Class::Pyro => Role::Offense,
+            Class::Demoman | Class::HeavyWeapons | Class::Engineer => Role::Defense,
+            Class::Medic | Class::Sniper | Class::Spy => Role::Support,
+        }
+    }
+}
+
+/// Role of a player class
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Role {
+    /// Scout, soldier and pyro
+    Offense,
+    /// Demoman, heavy and engineer
+    Defense,
+    /// Medic, sniper and spy
+    Support,
+}
+
+impl Display for Team {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(<&str>::from(*self), f)
+    }
+}
+
+impl From<Team> for &str {
+    fn from(team: Team) -> Self {
+        match team {
+            Team::Red => "red",
+            Team::Blue => "blue",
+        }
+    }
+}
+
+impl FromStr for Team {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "red" => Ok(Team::Red),
+            "blue" => Ok(Team::Blue),
+            _ => Err(ParseEnumError::new("team", s, "red, blue")),
+        }
+    }
+}
+
+impl Display for Class {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(<&str>::from(*self), f)
+    }
+}
+
+impl From<Class> for &str {
+    fn from(class: Class) -> Self {
+        match class {
+            Class::Scout => "scout",
+            Class::Soldier => "soldier",
+            Class::Pyro => "pyro",
+            Class::Demoman => "demoman",
+            Class::HeavyWeapons => "heavyweapons",
+            Class::Engineer => "engineer",
+            Class::Medic => "medic",
+            Class::Sniper => "sniper",
+            Class::Spy => "spy",
+        }
+    }
+}
+
+impl FromStr for Class {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lower = s.to_ascii_lowercase();
+        Class::all()
+            .into_iter()
+            .find(|class| <&str>::from(*class) == lower)
+            .ok_or_else(|| {
+                ParseEnumError::new(
+                    "class",
+                    s,
+                    "scout, soldier, pyro, demoman, heavyweapons, engineer, medic, sniper, spy",
+                )
+            })
+    }
+}
+
+#[test]
+fn test_parse_team_class() {
+    for team in [Team::Red, Team::Blue] {
+        assert_eq!(team, team.to_string().parse().unwrap());
+        assert_eq!(serde_json::to_value(team).unwrap(), team.to_string());
+    }
+    for class in Class::all() {
+        assert_eq!(class, class.to_string().parse().unwrap());
+        assert_eq!(serde_json::to_value(class).unwrap(), class.to_string());
+    }
+    assert_eq!(Team::Red, "RED".parse().unwrap());
+    assert_eq!(Class::Medic, "Medic".parse().unwrap());
+    assert!("green".parse::<Team>().is_err());
+    assert!("heavy".parse::<Class>().is_err());
+}
+
+#[test]
+fn test_class_role() {
+    assert_eq!(9, Class::all().len());
+    for role in [Role::Offense, Role::Defense, Role::Support] {
+        assert_eq!(
+            3,
+            Class::all()
+                .iter()
+                .filter(|class| class.role() == role)
+                .count()
+        );
+    }
+    assert_eq!(Role::Support, Class::Medic.role());
+    assert_eq!(Role::Offense, Class::Soldier.role());
+}
+
 /// Deserializes a lowercase hex string to a `[u8; 16]`.
 fn hex_to_digest<'de, D>(deserializer: D) -> Result<[u8; 16], D::Error>
 where
@@ -252,7 +1445,7 @@ where
     use hex::FromHex;
     use serde::de::Error;
 
-    let string = <&str>::deserialize(deserializer)?;
+    let string = String::deserialize(deserializer)?;
 
     if string.is_empty() {
         return Ok([0; 16]);
@@ -261,16 +1454,68 @@ where
     <[u8; 16]>::from_hex(string).map_err(|err| Error::custom(err.to_string()))
 }
 
+/// Serializes a `[u8; 16]` to a lowercase hex string, an all-zero digest is serialized as an empty string.
+fn digest_to_hex<S>(digest: &[u8; 16], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if digest == &[0; 16] {
+        serializer.serialize_str("")
+    } else {
+        serializer.serialize_str(&hex::encode(digest))
+    }
+}
+
 /// Chat message send in the demo
-#[derive(Clone, Debug, Deserialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
 pub struct ChatMessage {
     pub user: String,
+    /// Seconds since the start of the demo
     pub time: u32,
     pub message: String,
 }
 
+impl ChatMessage {
+    /// Time since the start of the demo
+    #[must_use]
+    pub fn timestamp(&self) -> Duration {
+        Duration::from_secs(u64::from(self.time))
+    }
+
+    /// Time since the start of the demo formatted as `mm:ss`
+    ///
+    /// Minutes are not wrapped into hours, a message after one hour and five seconds is formatted as `60:05`.
+    #[must_use]
+    pub fn formatted(&self) -> String {
+        format!("{:02}:{:02}", self.time / 60, self.time % 60)
+    }
+}
+
+/// Consecutive chat messages from the same user
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ChatGroup<'a> {
+    pub user: String,
+    pub messages: Vec<&'a ChatMessage>,
+}
+
+/// Group runs of consecutive chat messages from the same user
+#[must_use]
+pub fn group_chat(messages: &[ChatMessage]) -> Vec<ChatGroup<'_>> {
+    let mut groups: Vec<ChatGroup> = Vec::new();
+    for message in messages {
+        match groups.last_mut() {
+            Some(group) if group.user == message.user => group.messages.push(message),
+            _ => groups.push(ChatGroup {
+                user: message.user.clone(),
+                messages: vec![message],
+            }),
+        }
+    }
+    groups
+}
+
 /// Order for listing demos
-#[derive(Debug, Clone, Copy, Serialize, Default)]
+#[derive(Debug, Clone, Copy, Serialize, Default, PartialEq, Eq, Hash)]
 #[serde(into = "&str")]
 pub enum ListOrder {
     Ascending,
@@ -278,8 +1523,74 @@ pub enum ListOrder {
     Descending,
 }
 
+/// Normalized map name
+///
+/// Map names are lowercased and the `workshop/` prefix and `.ugc` id suffix of workshop maps are removed.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+#[serde(transparent)]
+pub struct MapName(String);
+
+impl MapName {
+    pub fn new(map: impl AsRef<str>) -> Self {
+        let map = map.as_ref().trim().to_lowercase();
+        let map = map.strip_prefix("workshop/").unwrap_or(&map);
+        let map = match map.rsplit_once(".ugc") {
+            Some((name, id)) if !id.is_empty() && id.bytes().all(|c| c.is_ascii_digit()) => name,
+            _ => map,
+        };
+        MapName(map.to_string())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// The game mode prefix of the map name, such as `cp`, `koth` or `pl`
+    ///
+    /// Returns `None` if the map name doesn't have a prefix
+    pub fn game_mode_prefix(&self) -> Option<&str> {
+        self.0
+            .split_once('_')
+            .map(|(prefix, _)| prefix)
+            .filter(|prefix| !prefix.is_empty())
+    }
+}
+
+impl Display for MapName {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(&self.0, f)
+    }
+}
+
+impl AsRef<str> for MapName {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<MapName> for String {
+    fn from(map: MapName) -> Self {
+        map.0
+    }
+}
+
+#[test]
+fn test_map_name() {
+    let map = MapName::new("Workshop/KOTH_Product_Final.ugc123456");
+    assert_eq!(map.as_str(), "koth_product_final");
+    assert_eq!(map.game_mode_prefix(), Some("koth"));
+    assert_eq!(
+        MapName::new("cp_process_final").game_mode_prefix(),
+        Some("cp")
+    );
+    assert_eq!(MapName::new("pl_upward.ugcx").as_str(), "pl_upward.ugcx");
+    assert_eq!(MapName::new("itemtest").game_mode_prefix(), None);
+    assert_eq!(MapName::new("_test").game_mode_prefix(), None);
+    assert_eq!(test_demo().map_name().game_mode_prefix(), Some("cp"));
+}
+
 /// Game type as recognized by demos.tf, HL, Prolander, 6s or 4v4
-#[derive(Debug, Clone, Copy, Serialize)]
+#[derive(Debug, Clone, Copy, Serialize, PartialEq, Eq, Hash)]
 pub enum GameType {
     #[serde(rename = "hl")]
     HL,
@@ -291,6 +1602,110 @@ pub enum GameType {
     Fours,
 }
 
+/// Storage backend a demo is stored on
+///
+/// Backends not known by this crate can be specified using `Backend::Custom`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Backend {
+    /// The main demos.tf storage
+    Static,
+    /// Cold storage for older demos
+    Freezer,
+    Custom(String),
+}
+
+impl Backend {
+    /// The name of the backend as used by the api
+    pub fn as_str(&self) -> &str {
+        match self {
+            Backend::Static => "static",
+            Backend::Freezer => "freezer",
+            Backend::Custom(name) => name.as_str(),
+        }
+    }
+}
+
+impl Display for Backend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(self.as_str(), f)
+    }
+}
+
+impl Serialize for Backend {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[test]
+fn test_serialize_backend() {
+    assert_eq!(
+        serde_json::json!("static"),
+        serde_json::to_value(Backend::Static).unwrap()
+    );
+    assert_eq!(
+        serde_json::json!("freezer"),
+        serde_json::to_value(Backend::Freezer).unwrap()
+    );
+    assert_eq!(
+        serde_json::json!("custom"),
+        serde_json::to_value(Backend::Custom("custom".into())).unwrap()
+    );
+    assert_eq!("freezer", Backend::Freezer.to_string());
+}
+
+impl Display for GameType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Display::fmt(<&str>::from(*self), f)
+    }
+}
+
+impl From<GameType> for &str {
+    fn from(ty: GameType) -> Self {
+        match ty {
+            GameType::HL => "hl",
+            GameType::Prolander => "prolander",
+            GameType::Sixes => "6v6",
+            GameType::Fours => "4v4",
+        }
+    }
+}
+
+impl FromStr for GameType {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "hl" => Ok(GameType::HL),
+            "prolander" => Ok(GameType::Prolander),
+            "6v6" => Ok(GameType::Sixes),
+            "4v4" => Ok(GameType::Fours),
+            _ => Err(ParseEnumError::new(
+                "game type",
+                s,
+                "hl, prolander, 6v6, 4v4",
+            )),
+        }
+    }
+}
+
+#[test]
+fn test_parse_game_type() {
+    for ty in [
+        GameType::HL,
+        GameType::Prolander,
+        GameType::Sixes,
+        GameType::Fours,
+    ] {
+        assert_eq!(ty, ty.to_string().parse().unwrap());
+    }
+    assert_eq!(GameType::HL, "HL".parse().unwrap());
+    assert_eq!(
+        "invalid game type \"5v5\", expected one of hl, prolander, 6v6, 4v4",
+        "5v5".parse::<GameType>().unwrap_err().to_string()
+    );
+}
+
 impl Display for ListOrder {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         Display::fmt(<&str>::from(*self), f)
@@ -306,23 +1721,53 @@ impl From<ListOrder> for &str {
     }
 }
 
+impl FromStr for ListOrder {
+    type Err = ParseEnumError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "asc" | "ascending" => Ok(ListOrder::Ascending),
+            "desc" | "descending" => Ok(ListOrder::Descending),
+            _ => Err(ParseEnumError::new("list order", s, "ASC, DESC")),
+        }
+    }
+}
+
+#[test]
+fn test_parse_list_order() {
+    for order in [ListOrder::Ascending, ListOrder::Descending] {
+        assert_eq!(ListOrder::from_str(&order.to_string()), Ok(order));
+    }
+    assert_eq!(ListOrder::from_str("asc"), Ok(ListOrder::Ascending));
+    assert_eq!(ListOrder::from_str("Descending"), Ok(ListOrder::Descending));
+    assert!(ListOrder::from_str("up").is_err());
+}
+
 /// Parameters for demo list command
 #[derive(Debug, Default, Serialize)]
 pub struct ListParams {
     order: ListOrder,
     backend: Option<String>,
     map: Option<String>,
+    name: Option<String>,
     players: PlayerList,
     #[serde(rename = "type")]
     ty: Option<GameType>,
-    #[serde(serialize_with = "serialize_option_time")]
-    after: Option<OffsetDateTime>,
-    #[serde(serialize_with = "serialize_option_time")]
-    before: Option<OffsetDateTime>,
+    #[cfg_attr(feature = "time", serde(serialize_with = "serialize_option_time"))]
+    after: Option<Timestamp>,
+    #[cfg_attr(feature = "time", serde(serialize_with = "serialize_option_time"))]
+    before: Option<Timestamp>,
     before_id: Option<u64>,
     after_id: Option<u64>,
+    uploader: Option<UserId>,
+    // not supported by the api, applied to the received demos instead
+    #[serde(skip)]
+    min_duration: Option<u16>,
+    #[serde(skip)]
+    max_duration: Option<u16>,
 }
 
+#[cfg(feature = "time")]
 fn serialize_option_time<S>(dt: &Option<OffsetDateTime>, serializer: S) -> Result<S::Ok, S::Error>
 where
     S: Serializer,
@@ -333,12 +1778,50 @@ where
     }
 }
 
+#[cfg(feature = "time")]
+fn timestamp(secs: i64) -> Result<Timestamp, Error> {
+    OffsetDateTime::from_unix_timestamp(secs).map_err(|_| Error::InvalidTimestamp(secs))
+}
+
+/// Range of unix timestamps supported by `OffsetDateTime`, from the start of year -9999 to the end of year 9999
+#[cfg(not(feature = "time"))]
+const TIMESTAMP_RANGE: std::ops::RangeInclusive<i64> = -377_705_116_800..=253_402_300_799;
+
+#[cfg(not(feature = "time"))]
+fn timestamp(secs: i64) -> Result<Timestamp, Error> {
+    if TIMESTAMP_RANGE.contains(&secs) {
+        Ok(secs)
+    } else {
+        Err(Error::InvalidTimestamp(secs))
+    }
+}
+
+fn is_valid_player(steam_id: &SteamID) -> bool {
+    steam_id.account_id() != 0
+        && steam_id.account_type() == AccountType::Individual
+        && steam_id.universe() != Universe::Invalid
+        && steam_id.instance() != Instance::Invalid
+}
+
+/// Players to filter with, stored inline for up to two full 6s rosters
 #[derive(Default, Debug)]
-struct PlayerList(TinyVec<[SteamID; 2]>);
+struct PlayerList(TinyVec<[SteamID; 12]>);
 
 impl PlayerList {
+    /// Create a player list, skipping duplicate steam ids
     fn new<T: Into<SteamID>, I: IntoIterator<Item = T>>(players: I) -> Self {
-        PlayerList(players.into_iter().map(Into::into).collect())
+        let mut list = PlayerList::default();
+        for player in players {
+            list.push(player.into());
+        }
+        list
+    }
+
+    /// Add a player to the list, if it isn't in the list yet
+    fn push(&mut self, player: SteamID) {
+        if !self.0.contains(&player) {
+            self.0.push(player);
+        }
     }
 }
 
@@ -384,6 +1867,16 @@ fn test_serialize_player_list() {
 }
 
 impl ListParams {
+    /// Create parameters for finding demos similar to `demo`, filtering by the map and backend of the demo
+    ///
+    /// Players and dates are intentionally left unset, they can be added with the other `with_*` methods.
+    #[must_use]
+    pub fn from_demo(demo: &Demo) -> Self {
+        ListParams::default()
+            .with_map(demo.map.as_str())
+            .with_backend(demo.backend.as_str())
+    }
+
     /// Specify the backend name to filter demos with
     #[must_use]
     pub fn with_backend(self, backend: impl Into<String>) -> Self {
@@ -393,6 +1886,79 @@ impl ListParams {
         }
     }
 
+    /// Specify the backend to filter demos with
+    ///
+    /// Use `with_backend` or `Backend::Custom` for backends not known by this crate
+    #[must_use]
+    pub fn with_backend_typed(self, backend: Backend) -> Self {
+        ListParams {
+            backend: Some(backend.as_str().into()),
+            ..self
+        }
+    }
+
+    /// Only include demos that are at least `secs` seconds long
+    ///
+    /// Since the api doesn't support filtering by duration, the filter is applied after each page is received,
+    /// pages can thus contain fewer demos than a full page even if more pages are available.
+    #[must_use]
+    pub fn with_min_duration(self, secs: u16) -> Self {
+        ListParams {
+            min_duration: Some(secs),
+            ..self
+        }
+    }
+
+    /// Only include demos that are at most `secs` seconds long
+    ///
+    /// Since the api doesn't support filtering by duration, the filter is applied after each page is received,
+    /// pages can thus contain fewer demos than a full page even if more pages are available.
+    #[must_use]
+    pub fn with_max_duration(self, secs: u16) -> Self {
+        ListParams {
+            max_duration: Some(secs),
+            ..self
+        }
+    }
+
+    /// Whether a demo matches the duration filters, which are applied by the client instead of the api
+    pub(crate) fn matches_duration(&self, demo: &Demo) -> bool {
+        self.matches_duration_secs(demo.duration)
+    }
+
+    fn matches_duration_secs(&self, duration: u16) -> bool {
+        self.min_duration.map_or(true, |min| duration >= min)
+            && self.max_duration.map_or(true, |max| duration <= max)
+    }
+
+    /// Whether the name of a demo contains the name filter, ignoring case
+    pub(crate) fn matches_name(&self, demo: &Demo) -> bool {
+        self.matches_name_str(&demo.name)
+    }
+
+    fn matches_name_str(&self, demo_name: &str) -> bool {
+        self.name.as_ref().map_or(true, |name| {
+            demo_name.to_lowercase().contains(&name.to_lowercase())
+        })
+    }
+
+    pub(crate) fn retain_matching(&self, demos: &mut Vec<Demo>) {
+        demos.retain(|demo| self.matches_duration(demo) && self.matches_name(demo));
+    }
+
+    /// Apply the client side filters to unprocessed demo data, demos without the filtered fields are kept
+    pub(crate) fn retain_matching_raw(&self, demos: &mut Vec<Value>) {
+        demos.retain(|demo| {
+            let duration = demo["duration"]
+                .as_u64()
+                .and_then(|duration| u16::try_from(duration).ok());
+            duration.map_or(true, |duration| self.matches_duration_secs(duration))
+                && demo["name"]
+                    .as_str()
+                    .map_or(true, |name| self.matches_name_str(name))
+        });
+    }
+
     /// Specify the map name to filter demos with
     #[must_use]
     pub fn with_map(self, map: impl Into<String>) -> Self {
@@ -402,6 +1968,34 @@ impl ListParams {
         }
     }
 
+    /// Only include demos with a name containing `query`, ignoring case
+    ///
+    /// The query is sent to the api as the `name` parameter and is also applied to the received demos,
+    /// so the results only contain substring matches even if the api matches differently.
+    #[must_use]
+    pub fn with_name(self, query: impl Into<String>) -> Self {
+        ListParams {
+            name: Some(query.into()),
+            ..self
+        }
+    }
+
+    /// Specify the player steam ids to filter demos with, rejecting ids that can't belong to a player
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidSteamId` for the first steam id that isn't a valid individual account in an existing universe
+    pub fn try_with_players<T: Into<SteamID>, I: IntoIterator<Item = T>>(
+        self,
+        players: I,
+    ) -> Result<Self, Error> {
+        let players = PlayerList::new(players);
+        if let Some(invalid) = players.0.iter().find(|steam_id| !is_valid_player(steam_id)) {
+            return Err(Error::InvalidSteamId(u64::from(*invalid)));
+        }
+        Ok(ListParams { players, ..self })
+    }
+
     /// Specify the player steam ids to filter demos with
     #[must_use]
     pub fn with_players<T: Into<SteamID>, I: IntoIterator<Item = T>>(self, players: I) -> Self {
@@ -411,6 +2005,13 @@ impl ListParams {
         }
     }
 
+    /// Add a player steam id to filter demos with, keeping the players that were already specified
+    #[must_use]
+    pub fn with_player<T: Into<SteamID>>(mut self, player: T) -> Self {
+        self.players.push(player.into());
+        self
+    }
+
     /// Specify the game type to filter demos with
     #[must_use]
     pub fn with_type(self, ty: GameType) -> Self {
@@ -422,7 +2023,7 @@ impl ListParams {
 
     /// Specify the before date to filter demos with
     #[must_use]
-    pub fn with_before(self, before: OffsetDateTime) -> Self {
+    pub fn with_before(self, before: Timestamp) -> Self {
         ListParams {
             before: Some(before),
             ..self
@@ -431,14 +2032,34 @@ impl ListParams {
 
     /// Specify the after date to filter demos with
     #[must_use]
-    pub fn with_after(self, after: OffsetDateTime) -> Self {
+    pub fn with_after(self, after: Timestamp) -> Self {
         ListParams {
             after: Some(after),
             ..self
         }
     }
 
-    /// Specify the maximum demo id to filter demos with
+    /// Specify the before date to filter demos with as unix timestamp
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidTimestamp` if the timestamp is outside the supported date range
+    pub fn with_before_timestamp(self, secs: i64) -> Result<Self, Error> {
+        Ok(self.with_before(timestamp(secs)?))
+    }
+
+    /// Specify the after date to filter demos with as unix timestamp
+    ///
+    /// # Errors
+    ///
+    /// Returns `Error::InvalidTimestamp` if the timestamp is outside the supported date range
+    pub fn with_after_timestamp(self, secs: i64) -> Result<Self, Error> {
+        Ok(self.with_after(timestamp(secs)?))
+    }
+
+    /// Only include demos with an id lower than `before`
+    ///
+    /// The bound is exclusive, the demo with id `before` itself is not included.
     #[must_use]
     pub fn with_before_id(self, before: u64) -> Self {
         ListParams {
@@ -447,7 +2068,10 @@ impl ListParams {
         }
     }
 
-    /// Specify the minimum demo id to filter demos with
+    /// Only include demos with an id higher than `after`
+    ///
+    /// The bound is exclusive, the demo with id `after` itself is not included. This makes it possible to
+    /// pass the highest id that has already been processed to get only new demos.
     #[must_use]
     pub fn with_after_id(self, after: u64) -> Self {
         ListParams {
@@ -456,9 +2080,714 @@ impl ListParams {
         }
     }
 
+    /// Specify the user id of the uploader to filter demos with
+    #[must_use]
+    pub fn with_uploader(self, uploader: impl Into<UserId>) -> Self {
+        ListParams {
+            uploader: Some(uploader.into()),
+            ..self
+        }
+    }
+
     /// Specify the sort
     #[must_use]
     pub fn with_order(self, order: ListOrder) -> Self {
         ListParams { order, ..self }
     }
 }
+
+#[test]
+fn test_list_params_from_demo() {
+    let params = ListParams::from_demo(&test_demo());
+    assert_eq!(params.map.as_deref(), Some("cp_gullywash_final1"));
+    assert_eq!(params.backend.as_deref(), Some("static"));
+    assert!(params.players.0.is_empty());
+    assert_eq!(params.after, None);
+}
+
+#[test]
+fn test_list_params_timestamp() {
+    let params = ListParams::default()
+        .with_before_timestamp(1_600_000_000)
+        .unwrap()
+        .with_after_timestamp(0)
+        .unwrap();
+    assert_eq!(params.before, Some(timestamp(1_600_000_000).unwrap()));
+    assert_eq!(params.after, Some(timestamp(0).unwrap()));
+    #[cfg(feature = "time")]
+    assert_eq!(params.after, Some(OffsetDateTime::UNIX_EPOCH));
+
+    // the last second of year 9999 is the latest supported time
+    assert!(ListParams::default()
+        .with_before_timestamp(253_402_300_799)
+        .is_ok());
+    assert!(matches!(
+        ListParams::default().with_before_timestamp(253_402_300_800),
+        Err(Error::InvalidTimestamp(253_402_300_800))
+    ));
+    assert!(matches!(
+        ListParams::default().with_after_timestamp(i64::MAX),
+        Err(Error::InvalidTimestamp(i64::MAX))
+    ));
+    assert!(matches!(
+        ListParams::default().with_after_timestamp(i64::MIN),
+        Err(Error::InvalidTimestamp(i64::MIN))
+    ));
+}
+
+#[cfg(test)]
+fn test_demo_json() -> serde_json::Value {
+    serde_json::json!({
+        "id": 1,
+        "url": "https://static.demos.tf/01/b2/01b2265d875026b91d59a2785abfd50d_test.dem",
+        "name": "test.dem",
+        "server": "Test server",
+        "duration": 1803,
+        "nick": "SourceTV",
+        "map": "cp_gullywash_final1",
+        "time": 1_600_000_000,
+        "red": "R",
+        "blue": "B",
+        "redScore": 5,
+        "blueScore": 3,
+        "playerCount": 12,
+        "uploader": {
+            "id": 1,
+            "steamid": "76561198024494988",
+            "name": "Icewind"
+        },
+        "hash": "01b2265d875026b91d59a2785abfd50d",
+        "backend": "static",
+        "path": "/demos/01/b2/01b2265d875026b91d59a2785abfd50d_test.dem",
+        "players": [{
+            "id": 1,
+            "user_id": 2,
+            "steamid": "76561198010628997",
+            "name": "freak u ___",
+            "team": "red",
+            "class": "heavyweapons",
+            "kills": 10,
+            "assists": 5,
+            "deaths": 3
+        }]
+    })
+}
+
+#[cfg(test)]
+fn test_demo() -> Demo {
+    serde_json::from_value(test_demo_json()).unwrap()
+}
+
+#[test]
+fn test_demo_round_trip() {
+    let json = test_demo_json();
+
+    let demo: Demo = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(json, serde_json::to_value(&demo).unwrap());
+
+    let mut listed = json.clone();
+    let listed = listed.as_object_mut().unwrap();
+    listed.remove("players");
+    listed.insert("uploader".into(), 1.into());
+    listed.insert("hash".into(), "".into());
+    let listed = serde_json::Value::Object(listed.clone());
+    let demo: Demo = serde_json::from_value(listed.clone()).unwrap();
+    assert_eq!(listed, serde_json::to_value(&demo).unwrap());
+}
+
+#[test]
+fn test_player_classes() {
+    let json = serde_json::json!({
+        "id": 1,
+        "user_id": 2,
+        "steamid": "76561198010628997",
+        "name": "freak u ___",
+        "team": "red",
+        "class": "soldier",
+        "kills": 10,
+        "assists": 5,
+        "deaths": 3
+    });
+    let player: Player = serde_json::from_value(json.clone()).unwrap();
+    assert!(player.classes.is_empty());
+
+    let mut json = json;
+    json["classes"] = serde_json::json!([
+        {"class": "soldier", "time": 700},
+        {"class": "medic", "time": 300}
+    ]);
+    let player: Player = serde_json::from_value(json).unwrap();
+    assert_eq!(player.class, Class::Soldier);
+    assert_eq!(
+        player.classes,
+        [
+            ClassPlaytime {
+                class: Class::Soldier,
+                time: 700
+            },
+            ClassPlaytime {
+                class: Class::Medic,
+                time: 300
+            }
+        ]
+    );
+}
+
+#[test]
+fn test_demo_winner() {
+    let mut demo = test_demo();
+
+    demo.red_score = 5;
+    demo.blue_score = 3;
+    assert_eq!(demo.winner(), Some(Team::Red));
+    assert_eq!(demo.score(Team::Red), 5);
+    assert_eq!(demo.score(Team::Blue), 3);
+    assert!(!demo.is_draw());
+
+    demo.red_score = 2;
+    demo.blue_score = 4;
+    assert_eq!(demo.winner(), Some(Team::Blue));
+    assert_eq!(demo.score(Team::Red), 2);
+    assert_eq!(demo.score(Team::Blue), 4);
+    assert!(!demo.is_draw());
+
+    demo.red_score = 5;
+    demo.blue_score = 5;
+    assert_eq!(demo.winner(), None);
+    assert!(demo.is_draw());
+}
+
+#[test]
+fn test_filter_players() {
+    let mut demo = test_demo();
+    assert_eq!(demo.players_on_team(Team::Red).count(), 1);
+    assert_eq!(demo.players_on_team(Team::Blue).count(), 0);
+    assert_eq!(demo.players_of_class(Class::HeavyWeapons).count(), 1);
+    assert_eq!(demo.players_of_class(Class::Scout).count(), 0);
+
+    demo.players = None;
+    assert_eq!(demo.players_on_team(Team::Red).count(), 0);
+    assert_eq!(demo.players_of_class(Class::HeavyWeapons).count(), 0);
+}
+
+#[test]
+fn test_demo_diff() {
+    let demo = test_demo();
+    let player = demo.players.as_ref().unwrap()[0].clone();
+    let mut other_user = player.user.clone();
+    other_user.steam_id = SteamID::from(76561197963701107);
+
+    let other = Demo {
+        red_score: demo.red_score + 2,
+        blue_score: demo.blue_score,
+        players: Some(vec![
+            Player {
+                kills: player.kills + 3,
+                deaths: player.deaths - 1,
+                ..player.clone()
+            },
+            Player {
+                user: other_user,
+                ..player.clone()
+            },
+        ]),
+        ..test_demo()
+    };
+
+    let diff = demo.diff(&other);
+    assert_eq!(2, diff.red_score_delta);
+    assert_eq!(0, diff.blue_score_delta);
+    assert_eq!(
+        vec![PlayerStatDelta {
+            steam_id: player.user.steam_id,
+            kills: 3,
+            assists: 0,
+            deaths: -1,
+        }],
+        diff.players
+    );
+
+    let listed = Demo {
+        players: None,
+        ..test_demo()
+    };
+    assert!(demo.diff(&listed).players.is_empty());
+}
+
+#[test]
+fn test_player_ratios() {
+    let player = Player {
+        kills: 10,
+        assists: 5,
+        deaths: 4,
+        ..test_demo().players.unwrap().remove(0)
+    };
+    assert_eq!(2.5, player.kdr());
+    assert_eq!(3.75, player.kda());
+    assert_eq!(15, player.total_score());
+
+    let deathless = Player {
+        deaths: 0,
+        ..player.clone()
+    };
+    assert_eq!(10.0, deathless.kdr());
+    assert_eq!(15.0, deathless.kda());
+
+    let one_death = Player {
+        deaths: 1,
+        ..player.clone()
+    };
+    assert_eq!(deathless.kdr(), one_death.kdr());
+
+    let idle = Player {
+        kills: 0,
+        assists: 0,
+        deaths: 0,
+        ..player
+    };
+    assert_eq!(0.0, idle.kdr());
+    assert_eq!(0.0, idle.kda());
+    assert_eq!(0, idle.total_score());
+}
+
+#[test]
+fn test_had_class() {
+    let mut demo = test_demo();
+    let player = demo.players.as_ref().unwrap()[0].clone();
+    demo.players = Some(vec![Player {
+        class: Class::Soldier,
+        classes: vec![
+            ClassPlaytime {
+                class: Class::Soldier,
+                time: 700,
+            },
+            ClassPlaytime {
+                class: Class::Medic,
+                time: 300,
+            },
+        ],
+        ..player
+    }]);
+
+    assert!(demo.had_class(Class::Soldier));
+    assert!(demo.had_class(Class::Medic));
+    assert!(!demo.had_class(Class::Spy));
+
+    demo.players = None;
+    assert!(!demo.had_class(Class::Soldier));
+}
+
+#[test]
+#[cfg(feature = "time")]
+fn test_demo_time() {
+    let demo = test_demo();
+    assert_eq!(UtcOffset::UTC, demo.time_utc().offset());
+    assert_eq!(demo.time, demo.time_utc());
+
+    let local = Demo {
+        time: demo.time.to_offset(UtcOffset::from_hms(2, 0, 0).unwrap()),
+        ..test_demo()
+    };
+    assert_eq!(UtcOffset::UTC, local.time_utc().offset());
+
+    assert!(demo.age() > Duration::from_secs(365 * 24 * 60 * 60));
+    let future = Demo {
+        time: OffsetDateTime::now_utc() + Duration::from_secs(60),
+        ..test_demo()
+    };
+    assert_eq!(Duration::ZERO, future.age());
+}
+
+#[test]
+#[cfg(not(feature = "time"))]
+fn test_demo_age() {
+    let demo = test_demo();
+    assert!(demo.age() > Duration::from_secs(365 * 24 * 60 * 60));
+    let future = Demo {
+        time: i64::MAX,
+        ..test_demo()
+    };
+    assert_eq!(Duration::ZERO, future.age());
+}
+
+#[test]
+fn test_chat_count() {
+    let demo = test_demo();
+    assert_eq!(None, demo.chat_count);
+    assert!(demo.has_chat());
+
+    let mut json = test_demo_json();
+    json["chatCount"] = 0.into();
+    let demo: Demo = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(Some(0), demo.chat_count);
+    assert!(!demo.has_chat());
+    assert!(!demo.extra.contains_key("chatCount"));
+    assert_eq!(json, serde_json::to_value(&demo).unwrap());
+
+    json["chatCount"] = 12.into();
+    let demo: Demo = serde_json::from_value(json).unwrap();
+    assert!(demo.has_chat());
+}
+
+#[test]
+fn test_display_demo() {
+    let demo = Demo {
+        red: "RED".into(),
+        blue: "BLU".into(),
+        red_score: 5,
+        blue_score: 3,
+        duration: 1800,
+        ..test_demo()
+    };
+    assert_eq!(
+        "#1 cp_gullywash_final1: RED 5-3 BLU (1800s)",
+        demo.to_string()
+    );
+}
+
+#[test]
+fn test_team_stats() {
+    let mut demo = test_demo();
+    let player = demo.players.as_ref().unwrap()[0].clone();
+    let players = vec![
+        Player {
+            kills: 200,
+            assists: 10,
+            deaths: 5,
+            ..player.clone()
+        },
+        Player {
+            kills: 100,
+            assists: 20,
+            deaths: 250,
+            ..player.clone()
+        },
+        Player {
+            team: Team::Blue,
+            kills: 7,
+            assists: 8,
+            deaths: 9,
+            ..player
+        },
+    ];
+
+    assert_eq!(
+        TeamStats::from_players(&players, Team::Red),
+        TeamStats {
+            kills: 300,
+            assists: 30,
+            deaths: 255,
+        }
+    );
+    assert_eq!(
+        TeamStats::from_players(&players, Team::Blue),
+        TeamStats {
+            kills: 7,
+            assists: 8,
+            deaths: 9,
+        }
+    );
+
+    demo.players = Some(players);
+    assert_eq!(demo.team_stats(Team::Blue).unwrap().kills, 7);
+
+    demo.players = None;
+    assert_eq!(demo.team_stats(Team::Red), None);
+}
+
+#[test]
+fn test_demo_extra_fields() {
+    let mut json = test_demo_json();
+    json["views"] = 42.into();
+
+    let demo: Demo = serde_json::from_value(json.clone()).unwrap();
+    assert_eq!(demo.extra.get("views"), Some(&Value::from(42)));
+    assert_eq!(demo.extra.len(), 1);
+    assert_eq!(json, serde_json::to_value(&demo).unwrap());
+
+    assert!(test_demo().extra.is_empty());
+}
+
+#[test]
+fn test_demo_user_equality() {
+    let demo = test_demo();
+    let renamed = Demo {
+        name: "other.dem".into(),
+        players: None,
+        ..demo.clone()
+    };
+    let other = Demo {
+        id: DemoId(2),
+        ..demo.clone()
+    };
+    assert_eq!(demo, renamed);
+    assert_ne!(demo, other);
+
+    let demos: std::collections::HashSet<Demo> =
+        [demo.clone(), renamed, other].into_iter().collect();
+    assert_eq!(demos.len(), 2);
+
+    let user = demo.uploader.user().unwrap().clone();
+    let renamed = User {
+        name: "other".into(),
+        ..user.clone()
+    };
+    assert_eq!(user, renamed);
+    assert_ne!(
+        user,
+        User {
+            id: UserId(2),
+            ..user.clone()
+        }
+    );
+}
+
+#[test]
+fn test_user_steam_id() {
+    let demo = test_demo();
+    let user = demo.uploader.user().unwrap();
+    assert_eq!(user.steam_id64(), 76561198024494988);
+    assert_eq!(
+        user.profile_url(),
+        "https://steamcommunity.com/profiles/76561198024494988"
+    );
+}
+
+#[test]
+fn test_user_avatar() {
+    let demo = test_demo();
+    assert_eq!(demo.uploader.user().unwrap().avatar, None);
+    assert_eq!(demo.players.as_ref().unwrap()[0].user.avatar, None);
+
+    let avatar =
+        "https://avatars.steamstatic.com/75b84075b70535c5cfb3499af03b3e4e7a7b556f_medium.jpg";
+    let mut json = test_demo_json();
+    json["uploader"]["avatar"] = avatar.into();
+    json["players"][0]["avatar"] = avatar.into();
+    let demo: Demo = serde_json::from_value(json).unwrap();
+    assert_eq!(
+        demo.uploader.user().unwrap().avatar.as_deref(),
+        Some(avatar)
+    );
+    assert_eq!(
+        demo.players.as_ref().unwrap()[0].user.avatar.as_deref(),
+        Some(avatar)
+    );
+
+    let round_tripped: Demo = serde_json::from_value(serde_json::to_value(&demo).unwrap()).unwrap();
+    assert_eq!(
+        round_tripped.players.unwrap()[0].user.avatar.as_deref(),
+        Some(avatar)
+    );
+}
+
+#[test]
+fn test_chat_message_time() {
+    let message = |time| ChatMessage {
+        user: "Icewind".into(),
+        time,
+        message: "gg".into(),
+    };
+    assert_eq!(message(0).timestamp(), Duration::ZERO);
+    assert_eq!(message(0).formatted(), "00:00");
+    assert_eq!(message(65).formatted(), "01:05");
+    assert_eq!(message(3725).timestamp(), Duration::from_secs(3725));
+    assert_eq!(message(3725).formatted(), "62:05");
+}
+
+#[test]
+fn test_group_chat() {
+    let message = |user: &str, time| ChatMessage {
+        user: user.into(),
+        time,
+        message: "gg".into(),
+    };
+    assert!(group_chat(&[]).is_empty());
+
+    let single = [message("a", 1)];
+    assert_eq!(
+        group_chat(&single),
+        [ChatGroup {
+            user: "a".into(),
+            messages: vec![&single[0]],
+        }]
+    );
+
+    let messages = [
+        message("a", 1),
+        message("a", 2),
+        message("b", 3),
+        message("a", 4),
+    ];
+    let groups = group_chat(&messages);
+    let summary: Vec<_> = groups
+        .iter()
+        .map(|group| (group.user.as_str(), group.messages.len()))
+        .collect();
+    assert_eq!(summary, [("a", 2), ("b", 1), ("a", 1)]);
+}
+
+#[test]
+fn test_demo_visibility() {
+    let demo = test_demo();
+    assert_eq!(demo.visibility, DemoVisibility::Public);
+    assert!(!demo.is_private());
+    assert!(serde_json::to_value(&demo)
+        .unwrap()
+        .get("visibility")
+        .is_none());
+
+    let mut json = test_demo_json();
+    json["visibility"] = "private".into();
+    let demo: Demo = serde_json::from_value(json).unwrap();
+    assert!(demo.is_private());
+    assert!(demo.extra.is_empty());
+    assert_eq!(
+        serde_json::to_value(&demo).unwrap()["visibility"],
+        serde_json::json!("private")
+    );
+}
+
+#[test]
+fn test_duration_filter() {
+    let demo = test_demo();
+    assert_eq!(demo.duration_as(), Duration::from_secs(1803));
+    assert!(ListParams::default().matches_duration(&demo));
+    assert!(ListParams::default()
+        .with_min_duration(300)
+        .with_max_duration(1803)
+        .matches_duration(&demo));
+    assert!(!ListParams::default()
+        .with_min_duration(1804)
+        .matches_duration(&demo));
+    assert!(!ListParams::default()
+        .with_max_duration(1800)
+        .matches_duration(&demo));
+}
+
+#[test]
+fn test_player_list_dedupe() {
+    assert_eq!(
+        "76561198024494988",
+        PlayerList::new([76561198024494988, 76561198024494988]).to_string()
+    );
+    assert_eq!(
+        "76561198024494988,76561197963701107",
+        ListParams::default()
+            .with_players([76561198024494988, 76561197963701107, 76561198024494988])
+            .with_player(76561197963701107)
+            .players
+            .to_string()
+    );
+}
+
+#[test]
+fn test_with_name() {
+    let params = ListParams::default()
+        .with_map("cp_gullywash_final1")
+        .with_name("ETF2L");
+    let request = reqwest::Client::new()
+        .get("https://api.demos.tf/demos")
+        .query(&params)
+        .build()
+        .unwrap();
+    assert_eq!(
+        Some("order=DESC&map=cp_gullywash_final1&name=ETF2L&players="),
+        request.url().query()
+    );
+
+    let mut demos = vec![
+        Demo {
+            name: "etf2l_season_40.dem".into(),
+            ..test_demo()
+        },
+        Demo {
+            name: "scrim.dem".into(),
+            ..test_demo()
+        },
+    ];
+    params.retain_matching(&mut demos);
+    assert_eq!(1, demos.len());
+    assert_eq!("etf2l_season_40.dem", demos[0].name);
+}
+
+#[test]
+fn test_retain_matching_raw() {
+    let params = ListParams::default()
+        .with_min_duration(600)
+        .with_name("etf2l");
+    let mut demos = vec![
+        serde_json::json!({"duration": 1800, "name": "ETF2L_final.dem"}),
+        serde_json::json!({"duration": 300, "name": "ETF2L_short.dem"}),
+        serde_json::json!({"duration": 1800, "name": "scrim.dem"}),
+        serde_json::json!({"id": 4}),
+    ];
+    params.retain_matching_raw(&mut demos);
+    assert_eq!(
+        demos,
+        [
+            serde_json::json!({"duration": 1800, "name": "ETF2L_final.dem"}),
+            serde_json::json!({"id": 4}),
+        ]
+    );
+}
+
+#[test]
+fn test_with_player() {
+    let params = ListParams::default()
+        .with_player(76561198024494988)
+        .with_player(76561197963701107);
+    assert_eq!(
+        "76561198024494988,76561197963701107",
+        params.players.to_string()
+    );
+
+    let params = ListParams::default()
+        .with_players([76561198024494988])
+        .with_player(76561197963701107);
+    assert_eq!(
+        "76561198024494988,76561197963701107",
+        params.players.to_string()
+    );
+}
+
+#[test]
+fn test_try_with_players() {
+    assert!(ListParams::default()
+        .try_with_players([76561198024494988, 76561197963701107])
+        .is_ok());
+    assert!(matches!(
+        ListParams::default().try_with_players([76561198024494988, 0]),
+        Err(Error::InvalidSteamId(0))
+    ));
+    // account id 0
+    assert!(ListParams::default()
+        .try_with_players([76561197960265728])
+        .is_err());
+    // clan id
+    assert!(ListParams::default()
+        .try_with_players([103582791429521408 + 1])
+        .is_err());
+}
+
+#[test]
+fn test_safe_filename() {
+    let filename = |name: &str| {
+        Demo {
+            name: name.into(),
+            ..test_demo()
+        }
+        .safe_filename()
+    };
+    assert_eq!("test.dem", filename("test.dem"));
+    assert_eq!("test.DEM", filename("test.DEM"));
+    assert_eq!("match.dem", filename("match"));
+    assert_eq!("_.._etc_passwd.dem", filename("../../etc/passwd"));
+    assert_eq!("a_b_c_d_.dem", filename("a\\b:c?d\n"));
+    assert_eq!("_nul.dem", filename("nul"));
+    assert_eq!("_COM1.txt.dem", filename("COM1.txt"));
+    assert_eq!("COM10.dem", filename("COM10"));
+    assert_eq!("demo.dem", filename(".."));
+    assert_eq!("demo.dem", filename(""));
+    assert_eq!("éé.dem", filename("éé"));
+}
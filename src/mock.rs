@@ -0,0 +1,257 @@
+use crate::client::{validate_page, PAGE_SIZE};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::ApiClient;
+use crate::{ChatMessage, Demo, DemoId, Error, ListOrder, ListParams, User, UserId};
+use std::collections::{BTreeMap, HashMap};
+use std::future::{ready, Future};
+
+/// Read access to the demos.tf api, implemented by both [`ApiClient`] and [`MockApiClient`]
+///
+/// Writing code against this trait instead of `ApiClient` allows testing it without an api server.
+///
+/// Requests on wasm can't be sent between threads, so `ApiClient` doesn't implement this trait there.
+pub trait DemoApi {
+    /// Get the data for a single demo
+    fn get(&self, demo_id: DemoId) -> impl Future<Output = Result<Demo, Error>> + Send;
+
+    /// List demos with the provided options, pages start counting at 1
+    fn list(
+        &self,
+        params: ListParams,
+        page: u32,
+    ) -> impl Future<Output = Result<Vec<Demo>, Error>> + Send;
+
+    /// Get user info by id
+    fn get_user(&self, user_id: UserId) -> impl Future<Output = Result<User, Error>> + Send;
+
+    /// Search for players by name
+    fn search_users(&self, name: &str) -> impl Future<Output = Result<Vec<User>, Error>> + Send;
+
+    /// Get the chat messages for a demo
+    fn get_chat(
+        &self,
+        demo_id: DemoId,
+    ) -> impl Future<Output = Result<Vec<ChatMessage>, Error>> + Send;
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl DemoApi for ApiClient {
+    fn get(&self, demo_id: DemoId) -> impl Future<Output = Result<Demo, Error>> + Send {
+        ApiClient::get(self, demo_id)
+    }
+
+    fn list(
+        &self,
+        params: ListParams,
+        page: u32,
+    ) -> impl Future<Output = Result<Vec<Demo>, Error>> + Send {
+        ApiClient::list(self, params, page)
+    }
+
+    fn get_user(&self, user_id: UserId) -> impl Future<Output = Result<User, Error>> + Send {
+        ApiClient::get_user(self, user_id)
+    }
+
+    fn search_users(&self, name: &str) -> impl Future<Output = Result<Vec<User>, Error>> + Send {
+        ApiClient::search_users(self, name)
+    }
+
+    fn get_chat(
+        &self,
+        demo_id: DemoId,
+    ) -> impl Future<Output = Result<Vec<ChatMessage>, Error>> + Send {
+        ApiClient::get_chat(self, demo_id)
+    }
+}
+
+/// Api client that serves demos, users and chat messages from memory
+///
+/// The game type filter of `ListParams` is not supported and ignored.
+///
+/// # Example
+///
+/// ```rust
+/// use demostf_client::{DemoApi, MockApiClient, Error};
+///
+/// async fn demo_name(api: &impl DemoApi, id: u32) -> Result<String, Error> {
+///     Ok(api.get(id.into()).await?.name)
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), demostf_client::Error> {
+/// let api = MockApiClient::new();
+/// assert!(demo_name(&api, 1).await.is_err());
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MockApiClient {
+    demos: BTreeMap<DemoId, Demo>,
+    users: BTreeMap<UserId, User>,
+    chat: HashMap<DemoId, Vec<ChatMessage>>,
+}
+
+impl MockApiClient {
+    /// Create a mock client without any data
+    #[must_use]
+    pub fn new() -> Self {
+        MockApiClient::default()
+    }
+
+    /// Add a demo to the mock data
+    #[must_use]
+    pub fn with_demo(mut self, demo: Demo) -> Self {
+        self.demos.insert(demo.id, demo);
+        self
+    }
+
+    /// Add a user to the mock data
+    #[must_use]
+    pub fn with_user(mut self, user: User) -> Self {
+        self.users.insert(user.id, user);
+        self
+    }
+
+    /// Add the chat messages of a demo to the mock data
+    #[must_use]
+    pub fn with_chat(mut self, demo_id: impl Into<DemoId>, chat: Vec<ChatMessage>) -> Self {
+        self.chat.insert(demo_id.into(), chat);
+        self
+    }
+
+    fn list_demos(&self, params: &ListParams, page: u32) -> Result<Vec<Demo>, Error> {
+        validate_page(page)?;
+
+        let matching = self.demos.values().filter(|demo| matches(demo, params));
+        let ordered: Vec<&Demo> = match params.order {
+            ListOrder::Ascending => matching.collect(),
+            ListOrder::Descending => matching.rev().collect(),
+        };
+
+        Ok(ordered
+            .into_iter()
+            .skip((page as usize - 1) * PAGE_SIZE)
+            .take(PAGE_SIZE)
+            .map(|demo| Demo {
+                // players are not included when listing demos
+                players: None,
+                ..demo.clone()
+            })
+            .collect())
+    }
+}
+
+fn matches(demo: &Demo, params: &ListParams) -> bool {
+    let players = demo.players.as_deref().unwrap_or_default();
+    params.map.as_ref().map_or(true, |map| &demo.map == map)
+        && params
+            .backend
+            .as_ref()
+            .map_or(true, |backend| &demo.backend == backend)
+        && params.after.map_or(true, |after| demo.time > after)
+        && params.before.map_or(true, |before| demo.time < before)
+        && params
+            .after_id
+            .map_or(true, |after_id| u64::from(demo.id.0) > after_id)
+        && params
+            .before_id
+            .map_or(true, |before_id| u64::from(demo.id.0) < before_id)
+        && params
+            .uploader
+            .map_or(true, |uploader| demo.uploader.id() == uploader)
+        && params.matches_duration(demo)
+        && params.matches_name(demo)
+        && params.players.0.iter().all(|steam_id| {
+            players
+                .iter()
+                .any(|player| player.user.steam_id == *steam_id)
+        })
+}
+
+impl DemoApi for MockApiClient {
+    fn get(&self, demo_id: DemoId) -> impl Future<Output = Result<Demo, Error>> + Send {
+        ready(
+            self.demos
+                .get(&demo_id)
+                .cloned()
+                .ok_or(Error::DemoNotFound(demo_id)),
+        )
+    }
+
+    fn list(
+        &self,
+        params: ListParams,
+        page: u32,
+    ) -> impl Future<Output = Result<Vec<Demo>, Error>> + Send {
+        ready(self.list_demos(&params, page))
+    }
+
+    fn get_user(&self, user_id: UserId) -> impl Future<Output = Result<User, Error>> + Send {
+        ready(
+            self.users
+                .get(&user_id)
+                .cloned()
+                .ok_or(Error::UserNotFound(user_id)),
+        )
+    }
+
+    fn search_users(&self, name: &str) -> impl Future<Output = Result<Vec<User>, Error>> + Send {
+        let name = name.to_lowercase();
+        ready(Ok(self
+            .users
+            .values()
+            .filter(|user| user.name.to_lowercase().contains(&name))
+            .cloned()
+            .collect()))
+    }
+
+    fn get_chat(
+        &self,
+        demo_id: DemoId,
+    ) -> impl Future<Output = Result<Vec<ChatMessage>, Error>> + Send {
+        ready(match self.chat.get(&demo_id) {
+            Some(chat) => Ok(chat.clone()),
+            None if self.demos.contains_key(&demo_id) => Ok(Vec::new()),
+            None => Err(Error::DemoNotFound(demo_id)),
+        })
+    }
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_mock_client() {
+    let demo = crate::test_demo();
+    let user = demo.uploader.user().unwrap().clone();
+    let api = MockApiClient::new()
+        .with_demo(Demo {
+            id: DemoId(2),
+            map: "koth_product_final".into(),
+            ..demo.clone()
+        })
+        .with_demo(demo)
+        .with_user(user);
+
+    assert_eq!(api.get(DemoId(1)).await.unwrap().id, DemoId(1));
+    assert!(matches!(
+        api.get(DemoId(3)).await.unwrap_err(),
+        Error::DemoNotFound(DemoId(3))
+    ));
+
+    let demos = api.list(ListParams::default(), 1).await.unwrap();
+    let ids: Vec<_> = demos.iter().map(|demo| demo.id).collect();
+    assert_eq!(ids, [DemoId(2), DemoId(1)]);
+    assert!(demos[0].players.is_none());
+
+    let demos = api
+        .list(ListParams::default().with_map("koth_product_final"), 1)
+        .await
+        .unwrap();
+    assert_eq!(demos.len(), 1);
+    assert!(api.list(ListParams::default(), 2).await.unwrap().is_empty());
+    assert!(api.list(ListParams::default(), 0).await.is_err());
+
+    assert_eq!(api.get_user(UserId(1)).await.unwrap().name, "Icewind");
+    assert_eq!(api.search_users("icew").await.unwrap().len(), 1);
+    assert!(api.get_chat(DemoId(1)).await.unwrap().is_empty());
+    assert!(api.get_chat(DemoId(3)).await.is_err());
+}
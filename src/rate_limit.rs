@@ -0,0 +1,62 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket rate limiter allowing bursts of up to one second worth of requests
+///
+/// Implemented as a generic cell rate algorithm, which tracks the time at which the bucket would be full
+/// again instead of counting tokens.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    interval: Duration,
+    burst: Duration,
+    /// Theoretical arrival time of the next request
+    next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+    pub fn new(requests_per_second: u32) -> Self {
+        let requests_per_second = requests_per_second.max(1);
+        let interval = Duration::from_secs(1) / requests_per_second;
+        RateLimiter {
+            interval,
+            burst: interval * (requests_per_second - 1),
+            next: Mutex::new(Instant::now()),
+        }
+    }
+
+    /// Reserve a slot for a request, returning how long to wait before the request can be sent
+    fn reserve(&self, now: Instant) -> Duration {
+        let mut next = self.next.lock().unwrap_or_else(|e| e.into_inner());
+        let arrival = (*next).max(now);
+        *next = arrival + self.interval;
+        arrival
+            .checked_sub(self.burst)
+            .map_or(Duration::ZERO, |allowed| {
+                allowed.saturating_duration_since(now)
+            })
+    }
+
+    /// Wait until a request can be sent
+    pub async fn acquire(&self) {
+        let wait = self.reserve(Instant::now());
+        if !wait.is_zero() {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[test]
+fn test_rate_limit() {
+    let limiter = RateLimiter::new(4);
+    let now = Instant::now();
+
+    // burst of 4 requests
+    for _ in 0..4 {
+        assert_eq!(limiter.reserve(now), Duration::ZERO);
+    }
+    assert_eq!(limiter.reserve(now), Duration::from_millis(250));
+    assert_eq!(limiter.reserve(now), Duration::from_millis(500));
+
+    let later = now + Duration::from_secs(10);
+    assert_eq!(limiter.reserve(later), Duration::ZERO);
+}
@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+/// Policy for retrying idempotent requests that failed with a server error or timeout
+///
+/// Retries are delayed using exponential backoff with jitter, starting at `base_delay` and
+/// doubling for every attempt up to `max_delay`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryConfig {
+    /// Maximum number of retries after the initial request
+    pub max_retries: u32,
+    /// Delay before the first retry
+    pub base_delay: Duration,
+    /// Upper bound for the delay between retries
+    pub max_delay: Duration,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        RetryConfig {
+            max_retries: 3,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(10),
+        }
+    }
+}
+
+impl RetryConfig {
+    /// Delay before the retry following the provided (zero based) attempt
+    ///
+    /// The delay is randomized between half and the full backoff delay to prevent concurrent requests
+    /// from retrying in lockstep
+    pub(crate) fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        backoff.mul_f64(0.5 + fastrand::f64() * 0.5)
+    }
+}
+
+#[test]
+fn test_retry_delay() {
+    let config = RetryConfig {
+        max_retries: 10,
+        base_delay: Duration::from_millis(100),
+        max_delay: Duration::from_secs(1),
+    };
+
+    for (attempt, expected) in [
+        (0, 100),
+        (1, 200),
+        (2, 400),
+        (3, 800),
+        (4, 1000),
+        (30, 1000),
+    ] {
+        let delay = config.delay(attempt);
+        let expected = Duration::from_millis(expected);
+        assert!(delay <= expected, "{delay:?} > {expected:?}");
+        assert!(delay >= expected / 2, "{delay:?} < {expected:?} / 2");
+    }
+}
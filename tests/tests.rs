@@ -1,4 +1,7 @@
-use demostf_client::{ApiClient, Error, ListOrder, ListParams};
+use demostf_client::{
+    ApiClient, DemoId, Error, ListOrder, ListParams, Md5Context, ResumeVerification, UserId,
+};
+use futures_util::{StreamExt, TryStreamExt};
 use sqlx::postgres::PgPoolOptions;
 use std::fs::read;
 use std::sync::atomic::{AtomicBool, Ordering};
@@ -93,7 +96,7 @@ async fn test_get_user() {
     assert_eq!("Icewind", user.name);
 }
 
-async fn upload(client: &ApiClient, source: &str, name: &str, red: &str, blue: &str) -> u32 {
+async fn upload(client: &ApiClient, source: &str, name: &str, red: &str, blue: &str) -> DemoId {
     let data = std::fs::read(source).unwrap();
 
     client
@@ -136,8 +139,8 @@ async fn test_list_demos() {
         .list(ListParams::default().with_order(ListOrder::Ascending), 1)
         .await
         .unwrap();
-    assert_eq!(demos[0].id, 1);
-    assert_eq!(demos[0].uploader.id(), 1);
+    assert_eq!(demos[0].id, DemoId(1));
+    assert_eq!(demos[0].uploader.id(), UserId(1));
     assert!(demos[0].uploader.user().is_none());
     assert_eq!(
         demos[0].uploader.resolve(&client).await.unwrap().steam_id,
@@ -150,13 +153,83 @@ async fn test_list_demos() {
     assert_eq!(demos[0].blue_score, 3);
 }
 
+#[tokio::test]
+async fn test_list_id_bounds() {
+    let client = test_client().await;
+
+    let ids = |params: ListParams| {
+        let client = &client;
+        async move {
+            client
+                .list(params.with_order(ListOrder::Ascending), 1)
+                .await
+                .unwrap()
+                .into_iter()
+                .map(|demo| demo.id)
+                .filter(|id| *id == DemoId(1))
+                .collect::<Vec<_>>()
+        }
+    };
+
+    // both bounds are exclusive
+    assert_eq!(
+        ids(ListParams::default().with_after_id(0)).await,
+        [DemoId(1)]
+    );
+    assert!(ids(ListParams::default().with_after_id(1)).await.is_empty());
+    assert_eq!(
+        ids(ListParams::default().with_before_id(2)).await,
+        [DemoId(1)]
+    );
+    assert!(ids(ListParams::default().with_before_id(1))
+        .await
+        .is_empty());
+    assert_eq!(
+        ids(ListParams::default().with_after_id(0).with_before_id(2)).await,
+        [DemoId(1)]
+    );
+}
+
+#[tokio::test]
+async fn test_list_stream() {
+    let client = test_client().await;
+
+    let demos: Vec<_> = client
+        .list_stream(ListParams::default().with_order(ListOrder::Ascending))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(demos.len(), 1);
+    assert_eq!(demos[0].id, DemoId(1));
+
+    let demos: Vec<_> = client
+        .list_stream(ListParams::default().with_players([76561198010628990]))
+        .try_collect()
+        .await
+        .unwrap();
+    assert_eq!(demos.len(), 0);
+}
+
+#[tokio::test]
+async fn test_list_page() {
+    let client = test_client().await;
+
+    let page = client
+        .list_page(ListParams::default().with_order(ListOrder::Ascending), 1)
+        .await
+        .unwrap();
+    assert_eq!(page.page, 1);
+    assert_eq!(page.demos.len(), 1);
+    assert!(!page.has_more);
+}
+
 #[tokio::test]
 async fn test_get_demo() {
     let client = test_client().await;
 
     let demo = client.get(1).await.unwrap();
-    assert_eq!(demo.id, 1);
-    assert_eq!(demo.uploader.id(), 1);
+    assert_eq!(demo.id, DemoId(1));
+    assert_eq!(demo.uploader.id(), UserId(1));
     assert!(demo.uploader.user().is_some());
     assert_eq!(
         demo.uploader.user().unwrap().steam_id,
@@ -179,6 +252,43 @@ async fn test_get_demo() {
     assert_eq!(players[0].user.name, "freak u ___");
 }
 
+#[tokio::test]
+async fn test_get_raw() {
+    let client = test_client().await;
+
+    let demo = client.get_raw(1).await.unwrap();
+    assert_eq!(demo["id"], 1);
+    assert_eq!(demo["name"], "test.dem");
+
+    let demos = client
+        .list_raw(ListParams::default().with_order(ListOrder::Ascending), 1)
+        .await
+        .unwrap();
+    assert_eq!(demos[0]["id"], 1);
+
+    assert!(matches!(
+        client.get_raw(999).await.unwrap_err(),
+        Error::DemoNotFound(DemoId(999))
+    ));
+}
+
+#[tokio::test]
+async fn test_get_many() {
+    let client = test_client().await;
+
+    let mut results: Vec<_> = client.get_many([1, 999], 2).collect().await;
+    results.sort_by_key(|(id, _)| *id);
+
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].0, DemoId(1));
+    assert_eq!(results[0].1.as_ref().unwrap().id, DemoId(1));
+    assert_eq!(results[1].0, DemoId(999));
+    assert!(matches!(
+        results[1].1.as_ref().unwrap_err(),
+        Error::DemoNotFound(DemoId(999))
+    ));
+}
+
 #[tokio::test]
 async fn test_get_chat() {
     let client = test_client().await;
@@ -222,6 +332,14 @@ async fn test_set_url_invalid_key() {
     assert!(matches!(res.unwrap_err(), Error::InvalidApiKey));
 }
 
+#[tokio::test]
+async fn test_delete_demo_invalid_key() {
+    let client = test_client().await;
+
+    let res = client.delete_demo(1, "wrong").await;
+    assert!(matches!(res.unwrap_err(), Error::InvalidApiKey));
+}
+
 #[tokio::test]
 async fn test_set_url_invalid_hash() {
     let client = test_client().await;
@@ -236,7 +354,7 @@ async fn test_set_url_invalid_hash() {
             "edit",
         )
         .await;
-    assert!(matches!(res.unwrap_err(), Error::HashMisMatch));
+    assert!(matches!(res.unwrap_err(), Error::HashMisMatch { .. }));
 }
 
 #[tokio::test]
@@ -254,7 +372,7 @@ async fn test_set_url_unknown_demo() {
         )
         .await;
     dbg!(&res);
-    assert!(matches!(res.unwrap_err(), Error::DemoNotFound(99)));
+    assert!(matches!(res.unwrap_err(), Error::DemoNotFound(DemoId(99))));
 }
 
 #[tokio::test]
@@ -287,7 +405,7 @@ async fn test_get_demo_not_found() {
 
     assert!(matches!(
         dbg!(client.get(999).await.unwrap_err()),
-        Error::DemoNotFound(999)
+        Error::DemoNotFound(DemoId(999))
     ));
 }
 
@@ -313,7 +431,18 @@ async fn test_list_upload() {
         )
         .await
         .unwrap();
-    assert_eq!(demos[0].id, 1);
+    assert_eq!(demos[0].id, DemoId(1));
+
+    let count = client
+        .upload_count(SteamID::from(76561198024494988))
+        .await
+        .unwrap();
+    assert_eq!(count, demos.len() as u64);
+    let count = client
+        .upload_count(SteamID::from(76561198024494987))
+        .await
+        .unwrap();
+    assert_eq!(count, 0);
 }
 
 #[tokio::test]
@@ -325,7 +454,7 @@ async fn test_list_players() {
         .await
         .unwrap();
     assert_eq!(demos.len(), 1);
-    assert_eq!(demos[0].id, 1);
+    assert_eq!(demos[0].id, DemoId(1));
 
     let demos = client
         .list(
@@ -335,7 +464,7 @@ async fn test_list_players() {
         .await
         .unwrap();
     assert_eq!(demos.len(), 1);
-    assert_eq!(demos[0].id, 1);
+    assert_eq!(demos[0].id, DemoId(1));
 
     let demos = client
         .list(ListParams::default().with_players([76561198010628990]), 1)
@@ -372,4 +501,89 @@ async fn test_download_demo() {
     demo.save(&client, &mut data).await.unwrap();
 
     assert_eq!(data.len(), read(test_demo_path()).unwrap().len());
+
+    let hash = demo.save_verified(&client, std::io::sink()).await.unwrap();
+    assert_eq!(hash, demo.hash);
+}
+
+#[tokio::test]
+async fn test_download_demo_progress() {
+    let client = test_client().await;
+
+    let mut demo = client.get(1).await.unwrap();
+
+    let demos_url =
+        std::env::var("API_ROOT").unwrap_or_else(|_| "http://localhost:8888/".to_string());
+
+    // fixup the url to one that is actually usable
+    demo.url = format!(
+        "{}static/01/b2/01b2265d875026b91d59a2785abfd50d_test.dem",
+        demos_url
+    );
+
+    let mut data: Vec<u8> = Vec::new();
+    let mut last_progress = (0, None);
+    demo.save_with_progress(&client, &mut data, |downloaded, total| {
+        assert!(downloaded > last_progress.0);
+        last_progress = (downloaded, total);
+    })
+    .await
+    .unwrap();
+
+    let expected = read(test_demo_path()).unwrap().len() as u64;
+    assert_eq!(last_progress.0, expected);
+    assert_eq!(data.len() as u64, expected);
+}
+
+#[tokio::test]
+async fn test_resume_download() {
+    let client = test_client().await;
+
+    let mut demo = client.get(1).await.unwrap();
+
+    let demos_url =
+        std::env::var("API_ROOT").unwrap_or_else(|_| "http://localhost:8888/".to_string());
+
+    // fixup the url to one that is actually usable
+    demo.url = format!(
+        "{}static/01/b2/01b2265d875026b91d59a2785abfd50d_test.dem",
+        demos_url
+    );
+
+    let expected = read(test_demo_path()).unwrap();
+    let mut data = expected[0..1024].to_vec();
+    let mut context = Md5Context::new();
+    context.consume(&data);
+
+    demo.resume_download(
+        &client,
+        &mut data,
+        1024,
+        ResumeVerification::Verify(context),
+    )
+    .await
+    .unwrap();
+    assert_eq!(data, expected);
+
+    let mut data = expected[0..1024].to_vec();
+    demo.resume_download(
+        &client,
+        &mut data,
+        1024,
+        ResumeVerification::SkipVerification,
+    )
+    .await
+    .unwrap();
+    assert_eq!(data, expected);
+
+    let mut data = expected[0..1024].to_vec();
+    let res = demo
+        .resume_download(
+            &client,
+            &mut data,
+            1024,
+            ResumeVerification::Verify(Md5Context::new()),
+        )
+        .await;
+    assert!(matches!(res.unwrap_err(), Error::HashMisMatch { .. }));
 }